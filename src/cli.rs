@@ -0,0 +1,392 @@
+use crate::cmd::LocaleType;
+use crate::convert::parse_language_file;
+use crate::diagnostic::{OutputFormat, Severity};
+use crate::encoding::{parse_codepage, parse_encoding};
+use crate::merge::{ConflictResolution, MergeStrategy, ReportFormat};
+use crate::model::ModelFormat;
+use crate::section::Language;
+use crate::sheet::SheetFormat;
+use encoding_rs::Encoding;
+use std::path::PathBuf;
+use structopt::clap::{Error, ErrorKind};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(
+    name = "localestring-merger",
+    about = "Merges and checks locale_string translation files. Runs interactively when no command is given.",
+    after_help = "EXIT CODES:\n    0  success\n    1  findings at or above the --fail-on severity\n    2  parse error\n    3  I/O error\n    64 invalid command line"
+)]
+pub struct Cli {
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+    /// Lowest severity of findings which makes the process fail: `warning` or `error`
+    #[structopt(long, global = true, default_value = "error")]
+    pub fail_on: Severity,
+}
+
+impl Cli {
+    /// Rejects combinations of arguments which clap cannot express.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(Command::Merge {
+            locale_type: LocaleType::LocaleGameInterface,
+            strategy,
+            report,
+            ..
+        }) = &self.command
+        {
+            if *strategy == MergeStrategy::PreferBase {
+                return Err(Error::with_description(
+                    "--strategy prefer-base cannot be used with --type client, client files \
+                    have no source language",
+                    ErrorKind::InvalidValue,
+                ));
+            }
+            if report.is_some() {
+                return Err(Error::with_description(
+                    "--report cannot be used with --type client",
+                    ErrorKind::ArgumentConflict,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub enum Command {
+    /// Merge newer translations into the current file
+    Merge {
+        /// File type: `server` (locale_string) or `client` (locale_game/locale_interface)
+        #[structopt(long = "type", default_value = "server")]
+        locale_type: LocaleType,
+        /// File containing current translations
+        #[structopt(long, parse(from_os_str))]
+        current: PathBuf,
+        /// File containing newer translations
+        #[structopt(long, parse(from_os_str))]
+        newer: PathBuf,
+        /// File to which merged translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Merge strategy: `overwrite`, `add-only`, `fill-gaps` or `prefer-base` (server-side only)
+        #[structopt(long, default_value = "overwrite")]
+        strategy: MergeStrategy,
+        /// Print a report of the changes: `text` or `json` (server-side only)
+        #[structopt(long)]
+        report: Option<ReportFormat>,
+        /// Encoding of input and output files, e.g. `cp1250`. Detected from the input when omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Merge any number of files, later inputs take precedence
+    MergeMany {
+        /// Files containing translations, from the lowest to the highest precedence
+        #[structopt(long = "input", parse(from_os_str), min_values = 2, required = true)]
+        inputs: Vec<PathBuf>,
+        /// File to which merged translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Merge strategy: `overwrite`, `add-only`, `fill-gaps` or `prefer-base`
+        #[structopt(long, default_value = "overwrite")]
+        strategy: MergeStrategy,
+        /// Print a report of the changes: `text` or `json`
+        #[structopt(long)]
+        report: Option<ReportFormat>,
+        /// Encoding of input and output files, e.g. `cp1250`. Detected from the input when omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Merge changes of two files branched from a common ancestor
+    Merge3 {
+        /// File containing the common ancestor of translations
+        #[structopt(long, parse(from_os_str))]
+        base: PathBuf,
+        /// File containing our translations
+        #[structopt(long, parse(from_os_str))]
+        ours: PathBuf,
+        /// File containing their translations
+        #[structopt(long, parse(from_os_str))]
+        theirs: PathBuf,
+        /// File to which merged translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Conflict resolution: `markers`, `ours` or `theirs`
+        #[structopt(long, default_value = "markers")]
+        resolve: ConflictResolution,
+        /// Encoding of input and output files, e.g. `cp1250`. Detected from the input when omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Convert an old two-line file into sections
+    Convert {
+        /// File containing old translations
+        #[structopt(long, parse(from_os_str))]
+        old: PathBuf,
+        /// Language of the translations in the old file
+        #[structopt(long)]
+        lang: Language,
+        /// File to which converted translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Encoding of input and output files, e.g. `cp1250`. Detected from the input when omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Combine client-side files of single languages into one file with sections
+    ClientToServer {
+        /// Client-side file of a language, e.g. `PL=locale_game.txt`. Repeat for every language
+        #[structopt(long = "input", parse(try_from_str = parse_language_file), min_values = 1, required = true)]
+        inputs: Vec<(Language, PathBuf)>,
+        /// File to which sections will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Encoding of the output file, e.g. `cp1250`. UTF-8 when omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Split a file with sections into client-side files, one per language
+    ServerToClient {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Directory in which a subdirectory per language will be created, e.g. `out/en`
+        #[structopt(long, parse(from_os_str))]
+        out_dir: PathBuf,
+        /// Name of the client-side files
+        #[structopt(long, default_value = "locale_game.txt")]
+        name: String,
+        /// Languages to export, e.g. `PL,EN`. Discovered from the file when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+        /// Codepage of a language, e.g. `TR=cp1254`. Known languages have default codepages
+        #[structopt(long, use_delimiter = true, parse(try_from_str = parse_codepage))]
+        codepage: Vec<(Language, &'static Encoding)>,
+    },
+    /// Find incomplete translations
+    FindIncomplete {
+        #[structopt(flatten)]
+        input: CheckInput,
+        /// File to which incomplete translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Expected languages, e.g. `PL,EN,DE`. Discovered from the file when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+    },
+    /// Check that placeholders match between translations
+    CheckArguments {
+        #[structopt(flatten)]
+        input: CheckInput,
+    },
+    /// Check that translations can be written in the codepage of their language
+    CheckCodepages {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Codepage of a language, e.g. `TR=cp1254`. Known languages have default codepages
+        #[structopt(long, use_delimiter = true, parse(try_from_str = parse_codepage))]
+        codepage: Vec<(Language, &'static Encoding)>,
+        /// Output format: `text`, `json` or `sarif`
+        #[structopt(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Check that color codes and hyperlinks are well-formed and match between translations
+    CheckMarkup {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Language the markup of other translations is compared against
+        #[structopt(long, default_value = "PL")]
+        reference: Language,
+        /// Output format: `text`, `json` or `sarif`
+        #[structopt(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Show translation progress of every language
+    Stats {
+        /// File type: `server` (locale_string) or `client` (locale_game/locale_interface)
+        #[structopt(long = "type", default_value = "server")]
+        locale_type: LocaleType,
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// File containing translations to compare (client-side only)
+        #[structopt(long, parse(from_os_str), required_if("locale-type", "client"))]
+        compare: Option<PathBuf>,
+        /// Languages to report, the first one is the source, e.g. `PL,EN,DE`. Discovered from
+        /// the file when omitted. For client files the languages of `--file` and `--compare`,
+        /// which are taken to be `PL,EN` when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+        /// Output format: `text` or `json`
+        #[structopt(long, default_value = "text")]
+        format: ReportFormat,
+    },
+    /// Export translations to a Gettext POT template and one PO file per language
+    ExportPo {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Directory to which `<stem>.pot` and `<stem>.<lang>.po` files will be saved
+        #[structopt(long, parse(from_os_str))]
+        out_dir: PathBuf,
+        /// Languages to export, the first one is the source, e.g. `PL,EN,DE`. Discovered from
+        /// the file when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+    },
+    /// Import translated Gettext PO files into a file
+    ImportPo {
+        /// File containing current translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// PO files with translations, each naming its language in the header
+        #[structopt(long, parse(from_os_str), min_values = 1, required = true)]
+        po: Vec<PathBuf>,
+        /// File to which merged translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Merge strategy: `overwrite`, `add-only`, `fill-gaps` or `prefer-base`
+        #[structopt(long, default_value = "overwrite")]
+        strategy: MergeStrategy,
+        /// Encoding of the input and output file, e.g. `cp1250`. Detected from the input when
+        /// omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Export translations to one XLIFF 2.0 file per target language
+    ExportXliff {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Directory to which the XLIFF files will be saved
+        #[structopt(long, parse(from_os_str))]
+        out_dir: PathBuf,
+        /// Languages to export, the first one is the source, e.g. `PL,EN,DE`. Discovered from
+        /// the file when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+    },
+    /// Import translated XLIFF 2.0 files into a file
+    ImportXliff {
+        /// File containing current translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// XLIFF files with translations, each naming its target language
+        #[structopt(long, parse(from_os_str), min_values = 1, required = true)]
+        xliff: Vec<PathBuf>,
+        /// File to which merged translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Merge strategy: `overwrite`, `add-only`, `fill-gaps` or `prefer-base`
+        #[structopt(long, default_value = "overwrite")]
+        strategy: MergeStrategy,
+        /// Encoding of the input and output file, e.g. `cp1250`. Detected from the input when
+        /// omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Export translations to a CSV or TSV sheet with one column per language
+    ExportSheet {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Sheet to which translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Language columns, e.g. `PL,EN,DE`. Discovered from the file when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+        /// Sheet format: `csv` or `tsv`. Chosen by the extension of `--out` when omitted
+        #[structopt(long)]
+        format: Option<SheetFormat>,
+    },
+    /// Import an edited CSV or TSV sheet into a file
+    ImportSheet {
+        /// File containing current translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// Sheet with a `label` column followed by one column per language
+        #[structopt(long, parse(from_os_str))]
+        sheet: PathBuf,
+        /// File to which merged translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Merge strategy: `overwrite`, `add-only`, `fill-gaps` or `prefer-base`
+        #[structopt(long, default_value = "overwrite")]
+        strategy: MergeStrategy,
+        /// Sheet format: `csv` or `tsv`. Chosen by the extension of `--sheet` when omitted
+        #[structopt(long)]
+        format: Option<SheetFormat>,
+        /// Encoding of the input and output file, e.g. `cp1250`. Detected from the input when
+        /// omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Dump translations to JSON or YAML as label → {language → text}
+    Dump {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// File to which translations will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Output format: `json` or `yaml`. Chosen by the extension of `--out` when omitted
+        #[structopt(long)]
+        format: Option<ModelFormat>,
+    },
+    /// Generate a file from translations dumped to JSON or YAML
+    Load {
+        /// JSON or YAML file containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// File to which generated sections will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Input format: `json` or `yaml`. Chosen by the extension of `--file` when omitted
+        #[structopt(long)]
+        format: Option<ModelFormat>,
+        /// Encoding of the output file, e.g. `cp1250`. UTF-8 when omitted
+        #[structopt(long, parse(try_from_str = parse_encoding))]
+        encoding: Option<&'static Encoding>,
+    },
+    /// Export labels translated to two or more languages as a TMX 1.4 translation memory
+    ExportTmx {
+        /// File containing translations
+        #[structopt(long, parse(from_os_str))]
+        file: PathBuf,
+        /// File to which the translation memory will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+        /// Languages to export, the first one is the source, e.g. `PL,EN,DE`. Discovered from
+        /// the file when omitted
+        #[structopt(long, use_delimiter = true)]
+        languages: Vec<Language>,
+    },
+    /// Find translations which are identical between languages
+    CheckDiversity {
+        #[structopt(flatten)]
+        input: CheckInput,
+        /// File to which translations without diversity will be saved
+        #[structopt(long, parse(from_os_str))]
+        out: PathBuf,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub struct CheckInput {
+    /// File type: `server` (locale_string) or `client` (locale_game/locale_interface)
+    #[structopt(long = "type", default_value = "server")]
+    pub locale_type: LocaleType,
+    /// File containing translations
+    #[structopt(long, parse(from_os_str))]
+    pub file: PathBuf,
+    /// File containing translations to compare (client-side only)
+    #[structopt(long, parse(from_os_str), required_if("locale-type", "client"))]
+    pub compare: Option<PathBuf>,
+    /// Output format: `text`, `json` or `sarif`
+    #[structopt(long, default_value = "text")]
+    pub format: OutputFormat,
+}
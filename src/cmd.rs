@@ -1,15 +1,38 @@
-use crate::convert::{convert_data, ConvertError};
-use crate::find::{find_incomplete_sections, find_missing_labels};
-use crate::parse::ParseError::ArgumentMismatch;
-use crate::parse::{merge_sections, parse_clientside, parse_data, read_file, ParseError};
-use crate::section::Language::{EN, PL};
-use crate::section::{check_string_arguments, Language};
+use crate::cli::{CheckInput, Cli, Command};
+use crate::convert::{convert_clientside, convert_data, generate_clientside, ConvertError};
+use crate::diagnostic::{exit_code, render_as, Diagnostic, ExitCode, OutputFormat, Severity};
+use crate::encoding::{read_encoded, write_encoded, Codepages};
+use crate::find::{find_incomplete_sections, find_missing_labels, find_missing_translations};
+use crate::merge::{
+    generate_with_conflicts, merge_clientside, merge_many, merge_three_way, resolve_conflict,
+    sources_comment, Conflict, ConflictResolution, MergeError, MergeReport, MergeStrategy,
+    ReportFormat, ThreeWayMerge, SOURCES_COMMENT,
+};
+use crate::model::{deserialize_sections, serialize_sections, ModelError, ModelFormat};
+use crate::parse::{
+    merge_sections, parse_clientside_entries, parse_clientside_recovering, parse_data,
+    parse_data_recovering, read_file, ParseError, ParsedData,
+};
+use crate::placeholder::{argument_types, format_argument_types};
+use crate::po::{generate_po, parse_po, PoError};
+use crate::section::{
+    borrowed_section, check_string_arguments, discover_languages, Language, Section,
+};
+use crate::sheet::{generate_sheet, parse_sheet, sheet_sections, SheetError, SheetFormat};
+use crate::stats::Stats;
+use crate::syntax::Document;
+use crate::tmx::generate_tmx;
+use crate::xliff::{generate_xliff, parse_xliff, XliffError};
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::{Input, Select};
+use encoding_rs::{Encoding, UTF_8};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum LocaleType {
     LocaleString,
     // serverside
@@ -27,6 +50,21 @@ impl Display for LocaleType {
     }
 }
 
+impl FromStr for LocaleType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "server" => Ok(LocaleType::LocaleString),
+            "client" => Ok(LocaleType::LocaleGameInterface),
+            t => Err(format!(
+                "invalid file type {}, expected server or client",
+                t
+            )),
+        }
+    }
+}
+
 impl From<usize> for LocaleType {
     fn from(v: usize) -> Self {
         match v {
@@ -40,20 +78,52 @@ impl From<usize> for LocaleType {
 #[derive(PartialEq)]
 pub enum Action {
     Merge,
+    MergeThreeWay,
+    MergeMany,
     Convert,
     FindIncomplete,
     CheckArguments,
     CheckTranslationsDiversity,
+    CheckCodepages,
+    CheckMarkup,
+    Stats,
+    ExportPo,
+    ImportPo,
+    ExportXliff,
+    ImportXliff,
+    ExportSheet,
+    ImportSheet,
+    Dump,
+    Load,
+    ExportTmx,
+    ClientToServer,
+    ServerToClient,
 }
 
 impl Display for Action {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Action::Merge => write!(f, "Merge translations"),
+            Action::MergeThreeWay => write!(f, "Merge translations with common ancestor"),
+            Action::MergeMany => write!(f, "Merge translations from many files"),
             Action::Convert => write!(f, "Convert old file"),
             Action::FindIncomplete => write!(f, "Find incomplete translations"),
             Action::CheckArguments => write!(f, "Check arguments"),
             Action::CheckTranslationsDiversity => write!(f, "Check translations diversity"),
+            Action::CheckCodepages => write!(f, "Check codepages"),
+            Action::CheckMarkup => write!(f, "Check color codes and hyperlinks"),
+            Action::Stats => write!(f, "Show translation statistics"),
+            Action::ExportPo => write!(f, "Export to Gettext PO files"),
+            Action::ImportPo => write!(f, "Import Gettext PO files"),
+            Action::ExportXliff => write!(f, "Export to XLIFF files"),
+            Action::ImportXliff => write!(f, "Import XLIFF files"),
+            Action::ExportSheet => write!(f, "Export to CSV/TSV sheet"),
+            Action::ImportSheet => write!(f, "Import CSV/TSV sheet"),
+            Action::Dump => write!(f, "Dump to JSON/YAML"),
+            Action::Load => write!(f, "Load from JSON/YAML"),
+            Action::ExportTmx => write!(f, "Export TMX translation memory"),
+            Action::ClientToServer => write!(f, "Convert client files to sections"),
+            Action::ServerToClient => write!(f, "Convert sections to client files"),
         }
     }
 }
@@ -62,32 +132,300 @@ impl From<usize> for Action {
     fn from(v: usize) -> Self {
         match v {
             0 => Action::Merge,
-            1 => Action::Convert,
-            2 => Action::FindIncomplete,
-            3 => Action::CheckArguments,
-            4 => Action::CheckTranslationsDiversity,
+            1 => Action::MergeThreeWay,
+            2 => Action::MergeMany,
+            3 => Action::Convert,
+            4 => Action::FindIncomplete,
+            5 => Action::CheckArguments,
+            6 => Action::CheckTranslationsDiversity,
+            7 => Action::CheckCodepages,
+            8 => Action::CheckMarkup,
+            9 => Action::Stats,
+            10 => Action::ExportPo,
+            11 => Action::ImportPo,
+            12 => Action::ExportXliff,
+            13 => Action::ImportXliff,
+            14 => Action::ExportSheet,
+            15 => Action::ImportSheet,
+            16 => Action::Dump,
+            17 => Action::Load,
+            18 => Action::ExportTmx,
+            19 => Action::ClientToServer,
+            20 => Action::ServerToClient,
             _ => unreachable!(),
         }
     }
 }
 
-impl From<usize> for Language {
-    fn from(l: usize) -> Self {
-        match l {
-            0 => PL,
-            1 => EN,
-            _ => unreachable!(),
+pub fn run() -> ExitCode {
+    let cli = match Cli::from_args_safe().and_then(|cli| cli.validate().map(|_| cli)) {
+        Ok(cli) => cli,
+        Err(e) if e.use_stderr() => {
+            eprintln!("{}", e.message);
+            return ExitCode::Usage;
         }
+        // help and version are printed on request
+        Err(e) => e.exit(),
+    };
+    match cli.command {
+        Some(command) => exit_code(&execute(command), cli.fail_on),
+        None => interactive().unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            ExitCode::IoError
+        }),
+    }
+}
+
+/// Runs the command, returning every problem it reported.
+fn execute(command: Command) -> Vec<Diagnostic> {
+    match command {
+        Command::Merge {
+            locale_type: LocaleType::LocaleString,
+            current,
+            newer,
+            out,
+            strategy,
+            report: report_format,
+            encoding,
+        } => report(merge(
+            &current,
+            &newer,
+            &out,
+            strategy,
+            report_format,
+            encoding,
+        )),
+        Command::Merge {
+            locale_type: LocaleType::LocaleGameInterface,
+            current,
+            newer,
+            out,
+            strategy,
+            encoding,
+            ..
+        } => report(merge_clientside_files(
+            &current, &newer, &out, strategy, encoding,
+        )),
+        Command::MergeMany {
+            inputs,
+            out,
+            strategy,
+            report: report_format,
+            encoding,
+        } => report(merge_files(
+            &inputs,
+            &out,
+            strategy,
+            report_format,
+            encoding,
+        )),
+        Command::Merge3 {
+            base,
+            ours,
+            theirs,
+            out,
+            resolve,
+            encoding,
+        } => report(merge_three_way_files(
+            &base,
+            &ours,
+            &theirs,
+            &out,
+            Some(resolve),
+            encoding,
+        )),
+        Command::Convert {
+            old,
+            lang,
+            out,
+            encoding,
+        } => report(convert(&old, &out, lang, encoding).map(|_| vec![])),
+        Command::ClientToServer {
+            inputs,
+            out,
+            encoding,
+        } => report(client_to_server(&inputs, &out, encoding)),
+        Command::ServerToClient {
+            file,
+            out_dir,
+            name,
+            languages,
+            codepage,
+        } => report(
+            server_to_client(
+                &file,
+                &out_dir,
+                &name,
+                &languages,
+                &codepage.into_iter().collect::<Codepages>(),
+            )
+            .map(|_| vec![]),
+        ),
+        Command::FindIncomplete {
+            input,
+            out,
+            languages,
+        } => {
+            let CheckInput {
+                locale_type,
+                file,
+                compare,
+                format,
+            } = input;
+            report_as(
+                find_incomplete(Some(&file), compare.as_ref(), &out, locale_type, &languages),
+                format,
+            )
+        }
+        Command::CheckArguments { input } => {
+            let CheckInput {
+                locale_type,
+                file,
+                compare,
+                format,
+            } = input;
+            report_as(
+                check_arguments(Some(&file), compare.as_ref(), locale_type),
+                format,
+            )
+        }
+        Command::CheckCodepages {
+            file,
+            codepage,
+            format,
+        } => report_as(
+            check_codepages(&file, &codepage.into_iter().collect::<Codepages>()),
+            format,
+        ),
+        Command::CheckMarkup {
+            file,
+            reference,
+            format,
+        } => report_as(check_markup(&file, reference), format),
+        Command::Stats {
+            locale_type,
+            file,
+            compare,
+            languages,
+            format,
+        } => {
+            report(stats(&file, compare.as_ref(), locale_type, &languages, format).map(|_| vec![]))
+        }
+        Command::ExportPo {
+            file,
+            out_dir,
+            languages,
+        } => report(export_po(&file, &out_dir, &languages).map(|_| vec![])),
+        Command::ImportPo {
+            file,
+            po,
+            out,
+            strategy,
+            encoding,
+        } => report(import_po(&file, &po, &out, strategy, encoding)),
+        Command::ExportXliff {
+            file,
+            out_dir,
+            languages,
+        } => report(export_xliff(&file, &out_dir, &languages).map(|_| vec![])),
+        Command::ImportXliff {
+            file,
+            xliff,
+            out,
+            strategy,
+            encoding,
+        } => report(import_xliff(&file, &xliff, &out, strategy, encoding)),
+        Command::ExportSheet {
+            file,
+            out,
+            languages,
+            format,
+        } => report(export_sheet(&file, &out, &languages, format).map(|_| vec![])),
+        Command::ImportSheet {
+            file,
+            sheet,
+            out,
+            strategy,
+            format,
+            encoding,
+        } => report(import_sheet(
+            &file, &sheet, &out, strategy, format, encoding,
+        )),
+        Command::Dump { file, out, format } => report(dump(&file, &out, format).map(|_| vec![])),
+        Command::Load {
+            file,
+            out,
+            format,
+            encoding,
+        } => report(load(&file, &out, format, encoding)),
+        Command::ExportTmx {
+            file,
+            out,
+            languages,
+        } => report(export_tmx(&file, &out, &languages).map(|_| vec![])),
+        Command::CheckDiversity { input, out } => {
+            let CheckInput {
+                locale_type,
+                file,
+                compare,
+                format,
+            } = input;
+            report_as(
+                check_diversity(Some(&file), compare.as_ref(), &out, locale_type),
+                format,
+            )
+        }
+    }
+}
+
+/// Prints every problem found by an action, or the error which stopped it.
+fn report<E>(result: Result<Vec<Diagnostic>, E>) -> Vec<Diagnostic>
+where
+    E: Into<Diagnostic>,
+{
+    report_as(result, OutputFormat::Text)
+}
+
+fn report_as<E>(result: Result<Vec<Diagnostic>, E>, format: OutputFormat) -> Vec<Diagnostic>
+where
+    E: Into<Diagnostic>,
+{
+    let diagnostics = match result {
+        Ok(diagnostics) => diagnostics,
+        Err(e) => vec![e.into()],
+    };
+
+    match render_as(&diagnostics, format) {
+        Ok(rendered) => print!("{}", rendered),
+        Err(e) => eprintln!("error: {}", e),
     }
+
+    diagnostics
 }
 
-pub fn run() -> std::io::Result<()> {
+fn interactive() -> std::io::Result<ExitCode> {
     let select_items = vec![
         Action::Merge,
+        Action::MergeThreeWay,
+        Action::MergeMany,
         Action::Convert,
         Action::FindIncomplete,
         Action::CheckArguments,
         Action::CheckTranslationsDiversity,
+        Action::CheckCodepages,
+        Action::CheckMarkup,
+        Action::Stats,
+        Action::ExportPo,
+        Action::ImportPo,
+        Action::ExportXliff,
+        Action::ImportXliff,
+        Action::ExportSheet,
+        Action::ImportSheet,
+        Action::Dump,
+        Action::Load,
+        Action::ExportTmx,
+        Action::ClientToServer,
+        Action::ServerToClient,
     ];
     let theme = &ColorfulTheme::default();
     let option: Action = Select::with_theme(theme)
@@ -97,15 +435,84 @@ pub fn run() -> std::io::Result<()> {
         .interact()?
         .into();
 
-    match option {
+    let diagnostics = match option {
         Action::Merge => {
+            let locale_types = vec![LocaleType::LocaleString, LocaleType::LocaleGameInterface];
+            let selected_locale_type: LocaleType = Select::with_theme(theme)
+                .with_prompt("Choose file type:")
+                .items(&locale_types)
+                .default(0)
+                .interact()?
+                .into();
+
+            let (cur_default, newer_default, save_default) =
+                if selected_locale_type == LocaleType::LocaleGameInterface {
+                    ("locale_game.txt", "locale_game2.txt", "locale_game_new.txt")
+                } else {
+                    (
+                        "locale_string.txt",
+                        "locale_string2.txt",
+                        "locale_string_new.txt",
+                    )
+                };
+
             let cur_file: String = Input::with_theme(theme)
                 .with_prompt("Enter the filename containing current translations")
-                .default("locale_string.txt".into())
+                .default(cur_default.into())
                 .interact_text()?;
 
             let newer_file: String = Input::with_theme(theme)
                 .with_prompt("Enter the filename containing newer translations")
+                .default(newer_default.into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which new translations will be saved")
+                .default(save_default.into())
+                .interact_text()?;
+
+            let mut strategies = MergeStrategy::variants();
+            if selected_locale_type == LocaleType::LocaleGameInterface {
+                strategies.retain(|s| *s != MergeStrategy::PreferBase);
+            }
+            let strategy = strategies[Select::with_theme(theme)
+                .with_prompt("Choose merge strategy:")
+                .items(&strategies)
+                .default(0)
+                .interact()?];
+
+            if selected_locale_type == LocaleType::LocaleGameInterface {
+                report(
+                    merge_clientside_files(&cur_file, &newer_file, &save_file, strategy, None)
+                        .map(|_| vec![]),
+                )
+            } else {
+                report(
+                    merge(
+                        &cur_file,
+                        &newer_file,
+                        &save_file,
+                        strategy,
+                        Some(ReportFormat::Text),
+                        None,
+                    )
+                    .map(|_| vec![]),
+                )
+            }
+        }
+        Action::MergeThreeWay => {
+            let base_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing the common ancestor of translations")
+                .default("locale_string_base.txt".into())
+                .interact_text()?;
+
+            let ours_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing our translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let theirs_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing their translations")
                 .default("locale_string2.txt".into())
                 .interact_text()?;
 
@@ -114,9 +521,47 @@ pub fn run() -> std::io::Result<()> {
                 .default("locale_string_new.txt".into())
                 .interact_text()?;
 
-            if let Err(e) = merge(&cur_file, &newer_file, &save_file) {
-                println!("Error: {:#?}", e);
+            report(merge_three_way_files(
+                &base_file,
+                &ours_file,
+                &theirs_file,
+                &save_file,
+                None,
+                None,
+            ))
+        }
+        Action::MergeMany => {
+            let mut files: Vec<String> = vec![];
+            loop {
+                let file: String = Input::with_theme(theme)
+                    .with_prompt(format!(
+                        "Enter the filename of input {} (later inputs take precedence, empty to finish)",
+                        files.len() + 1
+                    ))
+                    .allow_empty(true)
+                    .interact_text()?;
+                if file.is_empty() {
+                    break;
+                }
+                files.push(file);
             }
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which new translations will be saved")
+                .default("locale_string_new.txt".into())
+                .interact_text()?;
+
+            let strategies = MergeStrategy::variants();
+            let strategy = strategies[Select::with_theme(theme)
+                .with_prompt("Choose merge strategy:")
+                .items(&strategies)
+                .default(0)
+                .interact()?];
+
+            report(
+                merge_files(&files, &save_file, strategy, Some(ReportFormat::Text), None)
+                    .map(|_| vec![]),
+            )
         }
         Action::Convert => {
             let old_file: String = Input::with_theme(theme)
@@ -124,22 +569,25 @@ pub fn run() -> std::io::Result<()> {
                 .default("locale_string_old.txt".into())
                 .interact_text()?;
 
-            let languages = vec![PL, EN];
-            let lang: Language = Select::with_theme(theme)
+            let lang: String = Input::with_theme(theme)
                 .with_prompt("What language is in the file?")
-                .items(&languages)
-                .default(0)
-                .interact()?
-                .into();
+                .default("PL".into())
+                .validate_with(|l: &String| {
+                    if Language::is_code(l.trim().to_ascii_uppercase()) {
+                        Ok(())
+                    } else {
+                        Err("language code must consist of letters only")
+                    }
+                })
+                .interact_text()?;
+            let lang = Language::from(lang.as_str());
 
             let new_file: String = Input::with_theme(theme)
                 .with_prompt("Enter the filename to which converted translations will be saved")
                 .default("locale_string_new.txt".into())
                 .interact_text()?;
 
-            if let Err(e) = convert(&old_file, &new_file, lang) {
-                println!("Error: {:#?}", e);
-            }
+            report(convert(&old_file, &new_file, lang, None).map(|_| vec![]))
         }
         Action::FindIncomplete => {
             let locale_types = vec![LocaleType::LocaleString, LocaleType::LocaleGameInterface];
@@ -152,6 +600,7 @@ pub fn run() -> std::io::Result<()> {
 
             let file: Option<String>;
             let mut second_file: Option<String> = None;
+            let mut languages = vec![];
 
             if selected_locale_type == LocaleType::LocaleGameInterface {
                 file = Some(
@@ -173,6 +622,12 @@ pub fn run() -> std::io::Result<()> {
                         .default("locale_string.txt".into())
                         .interact_text()?,
                 );
+
+                let expected: String = Input::with_theme(theme)
+                    .with_prompt("Enter expected languages, e.g. PL,EN (empty to discover them)")
+                    .allow_empty(true)
+                    .interact_text()?;
+                languages = Language::parse_list(expected);
             }
 
             let save_file: String = Input::with_theme(theme)
@@ -180,14 +635,13 @@ pub fn run() -> std::io::Result<()> {
                 .default("locale_string_incomplete.txt".into())
                 .interact_text()?;
 
-            if let Err(e) = find_incomplete(
+            report(find_incomplete(
                 file.as_ref(),
                 second_file.as_ref(),
                 &save_file,
                 selected_locale_type,
-            ) {
-                println!("Error: {:#?}", e);
-            }
+                &languages,
+            ))
         }
         Action::CheckArguments | Action::CheckTranslationsDiversity => {
             let locale_types = vec![LocaleType::LocaleString, LocaleType::LocaleGameInterface];
@@ -231,25 +685,315 @@ pub fn run() -> std::io::Result<()> {
                     .default("locale_string_no_diversity.txt".into())
                     .interact_text()?;
 
-                if let Err(e) = check_diversity(
+                report(check_diversity(
                     file.as_ref(),
                     second_file.as_ref(),
                     &save_file,
                     selected_locale_type,
-                ) {
-                    println!("Error: {:#?}", e);
-                }
+                ))
             } else {
-                if let Err(e) =
-                    check_arguments(file.as_ref(), second_file.as_ref(), selected_locale_type)
-                {
-                    println!("Error: {:#?}", e);
-                }
+                report(check_arguments(
+                    file.as_ref(),
+                    second_file.as_ref(),
+                    selected_locale_type,
+                ))
             }
         }
-    }
+        Action::CheckCodepages => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
 
-    Ok(())
+            report(check_codepages(&file, &Codepages::default()))
+        }
+        Action::CheckMarkup => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let reference: String = Input::with_theme(theme)
+                .with_prompt("Which language should other translations be compared against?")
+                .default("PL".into())
+                .validate_with(|l: &String| {
+                    if Language::is_code(l.trim().to_ascii_uppercase()) {
+                        Ok(())
+                    } else {
+                        Err("language code must consist of letters only")
+                    }
+                })
+                .interact_text()?;
+
+            report(check_markup(&file, Language::from(reference.as_str())))
+        }
+        Action::Stats => {
+            let locale_types = vec![LocaleType::LocaleString, LocaleType::LocaleGameInterface];
+            let selected_locale_type: LocaleType = Select::with_theme(theme)
+                .with_prompt("Choose file type:")
+                .items(&locale_types)
+                .default(0)
+                .interact()?
+                .into();
+
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+            let mut second_file: Option<String> = None;
+            if selected_locale_type == LocaleType::LocaleGameInterface {
+                second_file = Some(
+                    Input::with_theme(theme)
+                        .with_prompt("Enter the filename containing translations to compare")
+                        .default("locale_game2.txt".into())
+                        .interact_text()?,
+                );
+            }
+
+            let languages: String = Input::with_theme(theme)
+                .with_prompt("Enter languages, source first, e.g. PL,EN (empty to discover them)")
+                .allow_empty(true)
+                .interact_text()?;
+
+            report(
+                stats(
+                    &file,
+                    second_file.as_ref(),
+                    selected_locale_type,
+                    &Language::parse_list(languages),
+                    ReportFormat::Text,
+                )
+                .map(|_| vec![]),
+            )
+        }
+        Action::ExportPo => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let out_dir: String = Input::with_theme(theme)
+                .with_prompt("Enter the directory to which PO files will be saved")
+                .default("po".into())
+                .interact_text()?;
+
+            let languages: String = Input::with_theme(theme)
+                .with_prompt("Enter languages, source first, e.g. PL,EN (empty to discover them)")
+                .allow_empty(true)
+                .interact_text()?;
+
+            report(export_po(&file, &out_dir, &Language::parse_list(languages)).map(|_| vec![]))
+        }
+        Action::ImportPo => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing current translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let po_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the PO filename containing translations")
+                .default("locale_string.en.po".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which merged translations will be saved")
+                .default("locale_string_merged.txt".into())
+                .interact_text()?;
+
+            report(import_po(
+                &file,
+                &[po_file],
+                &save_file,
+                MergeStrategy::Overwrite,
+                None,
+            ))
+        }
+        Action::ExportXliff => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let out_dir: String = Input::with_theme(theme)
+                .with_prompt("Enter the directory to which XLIFF files will be saved")
+                .default("xliff".into())
+                .interact_text()?;
+
+            let languages: String = Input::with_theme(theme)
+                .with_prompt("Enter languages, source first, e.g. PL,EN (empty to discover them)")
+                .allow_empty(true)
+                .interact_text()?;
+
+            report(export_xliff(&file, &out_dir, &Language::parse_list(languages)).map(|_| vec![]))
+        }
+        Action::ImportXliff => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing current translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let xliff_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the XLIFF filename containing translations")
+                .default("en.xlf".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which merged translations will be saved")
+                .default("locale_string_merged.txt".into())
+                .interact_text()?;
+
+            report(import_xliff(
+                &file,
+                &[xliff_file],
+                &save_file,
+                MergeStrategy::Overwrite,
+                None,
+            ))
+        }
+        Action::ExportSheet => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt(
+                    "Enter the sheet filename (.csv or .tsv) to which translations will be saved",
+                )
+                .default("locale_string.csv".into())
+                .interact_text()?;
+
+            let languages: String = Input::with_theme(theme)
+                .with_prompt("Enter languages, e.g. PL,EN (empty to discover them)")
+                .allow_empty(true)
+                .interact_text()?;
+
+            report(
+                export_sheet(&file, &save_file, &Language::parse_list(languages), None)
+                    .map(|_| vec![]),
+            )
+        }
+        Action::ImportSheet => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing current translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let sheet: String = Input::with_theme(theme)
+                .with_prompt("Enter the sheet filename (.csv or .tsv) containing translations")
+                .default("locale_string.csv".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which merged translations will be saved")
+                .default("locale_string_merged.txt".into())
+                .interact_text()?;
+
+            report(import_sheet(
+                &file,
+                &sheet,
+                &save_file,
+                MergeStrategy::Overwrite,
+                None,
+                None,
+            ))
+        }
+        Action::Dump => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt(
+                    "Enter the filename (.json or .yaml) to which translations will be saved",
+                )
+                .default("locale_string.json".into())
+                .interact_text()?;
+
+            report(dump(&file, &save_file, None).map(|_| vec![]))
+        }
+        Action::Load => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename (.json or .yaml) containing translations")
+                .default("locale_string.json".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which sections will be saved")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            report(load(&file, &save_file, None, None))
+        }
+        Action::ExportTmx => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which the translation memory will be saved")
+                .default("locale_string.tmx".into())
+                .interact_text()?;
+
+            let languages: String = Input::with_theme(theme)
+                .with_prompt("Enter languages, source first, e.g. PL,EN (empty to discover them)")
+                .allow_empty(true)
+                .interact_text()?;
+
+            report(export_tmx(&file, &save_file, &Language::parse_list(languages)).map(|_| vec![]))
+        }
+        Action::ClientToServer => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing PL translations")
+                .default("locale_game.txt".into())
+                .interact_text()?;
+
+            let second_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing EN translations")
+                .default("locale_game_en.txt".into())
+                .interact_text()?;
+
+            let save_file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename to which sections will be saved")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            report(client_to_server(
+                &[
+                    (Language::PL, PathBuf::from(file)),
+                    (Language::EN, PathBuf::from(second_file)),
+                ],
+                &save_file,
+                None,
+            ))
+        }
+        Action::ServerToClient => {
+            let file: String = Input::with_theme(theme)
+                .with_prompt("Enter the filename containing translations")
+                .default("locale_string.txt".into())
+                .interact_text()?;
+
+            let out_dir: String = Input::with_theme(theme)
+                .with_prompt("Enter the directory in which client files will be saved")
+                .default("client".into())
+                .interact_text()?;
+
+            report(
+                server_to_client(
+                    &file,
+                    &out_dir,
+                    "locale_game.txt",
+                    &[],
+                    &Codepages::default(),
+                )
+                .map(|_| vec![]),
+            )
+        }
+    };
+
+    Ok(exit_code(&diagnostics, Severity::Error))
 }
 
 fn check_diversity<T>(
@@ -257,27 +1001,53 @@ fn check_diversity<T>(
     secondary_file: Option<T>,
     save_file: T,
     locale_type: LocaleType,
-) -> Result<(), ParseError>
+) -> Result<Vec<Diagnostic>, ParseError>
 where
     T: AsRef<Path>,
 {
-    let first_file_data = &*read_file(file.unwrap())?;
+    let file = file.unwrap();
+    let first_file_data = &*read_file(&file)?;
+    let mut problems = vec![];
     match locale_type {
         LocaleType::LocaleString => {
-            let sections = parse_data(first_file_data)?;
-            let missing_diversity_sections: String = sections
-                .iter()
-                .filter(|s| !s.check_translations_diversity())
-                .map(|s| format!("{}\n", s.label))
-                .collect();
+            let ParsedData {
+                sections,
+                locations,
+                errors,
+                warnings,
+                ..
+            } = parse_data_recovering(first_file_data).with_file(&file);
+            problems.extend(errors.into_iter().map(Diagnostic::from).chain(warnings));
 
-            let _ = std::fs::write(save_file, &missing_diversity_sections)?;
+            let mut missing_diversity_sections = String::new();
+            for (s, location) in sections.iter().zip(locations) {
+                if !s.check_translations_diversity() {
+                    missing_diversity_sections.push_str(&format!("{}\n", s.label));
+                }
+                problems.extend(
+                    s.identical_translations()
+                        .into_iter()
+                        .map(|d| d.with_location(Some(location.clone()))),
+                );
+            }
+
+            std::fs::write(save_file, &missing_diversity_sections)?;
         }
         LocaleType::LocaleGameInterface => {
-            let second_file_data = &*read_file(secondary_file.unwrap())?;
+            let secondary_file = secondary_file.unwrap();
+            let second_file_data = &*read_file(&secondary_file)?;
 
-            let map_first = parse_clientside(first_file_data)?;
-            let map_second = parse_clientside(second_file_data)?;
+            let mut first = parse_clientside_recovering(first_file_data).with_file(&file);
+            let mut second =
+                parse_clientside_recovering(second_file_data).with_file(&secondary_file);
+            problems.extend(
+                first
+                    .errors
+                    .drain(..)
+                    .chain(second.errors.drain(..))
+                    .map(Diagnostic::from),
+            );
+            let (map_first, map_second) = (first.map(), second.map());
 
             let mut diversity_vec = vec![];
 
@@ -285,13 +1055,21 @@ where
                 if let Some(map_value) = map_second.get(k) {
                     if map_value == v {
                         diversity_vec.push(v);
+                        problems.push(
+                            Diagnostic::warning(
+                                "identical-translation",
+                                format!("label {} is identical in both files", k),
+                            )
+                            .with_label(*k)
+                            .with_location(first.location(k).cloned()),
+                        );
                     }
                 }
             }
 
-            let _ = std::fs::write(
+            std::fs::write(
                 save_file,
-                &diversity_vec
+                diversity_vec
                     .iter()
                     .map(|s| format!("{}\n", *s))
                     .collect::<String>(),
@@ -299,44 +1077,134 @@ where
         }
     }
 
-    Ok(())
+    Ok(problems)
 }
 
 fn check_arguments<T>(
     file: Option<T>,
     secondary_file: Option<T>,
     locale_type: LocaleType,
-) -> Result<(), ParseError>
+) -> Result<Vec<Diagnostic>, ParseError>
 where
     T: AsRef<Path>,
 {
-    let first_file_data = &*read_file(file.unwrap())?;
+    let file = file.unwrap();
+    let first_file_data = &*read_file(&file)?;
+    let mut problems = vec![];
     match locale_type {
         LocaleType::LocaleString => {
-            let sections = parse_data(first_file_data)?;
-            for s in &sections {
-                if !s.check_translations_arguments() {
-                    return Err(ParseError::ArgumentMismatch(s.label.to_string()));
-                }
+            let ParsedData {
+                sections,
+                locations,
+                errors,
+                warnings,
+                ..
+            } = parse_data_recovering(first_file_data).with_file(&file);
+            problems.extend(errors.into_iter().map(Diagnostic::from).chain(warnings));
+
+            for (s, location) in sections.iter().zip(locations) {
+                problems.extend(
+                    s.argument_mismatches()
+                        .into_iter()
+                        .map(|d| d.with_location(Some(location.clone()))),
+                );
             }
         }
         LocaleType::LocaleGameInterface => {
-            let second_file_data = &*read_file(secondary_file.unwrap())?;
+            let secondary_file = secondary_file.unwrap();
+            let second_file_data = &*read_file(&secondary_file)?;
 
-            let map_first = parse_clientside(first_file_data)?;
-            let map_second = parse_clientside(second_file_data)?;
+            let mut first = parse_clientside_recovering(first_file_data).with_file(&file);
+            let mut second =
+                parse_clientside_recovering(second_file_data).with_file(&secondary_file);
+            problems.extend(
+                first
+                    .errors
+                    .drain(..)
+                    .chain(second.errors.drain(..))
+                    .map(Diagnostic::from),
+            );
+            let (map_first, map_second) = (first.map(), second.map());
 
             for (k, v) in map_first.iter() {
                 if let Some(map_value) = map_second.get(k) {
                     if !check_string_arguments(v, map_value) {
-                        return Err(ArgumentMismatch(k.to_owned()));
+                        problems.push(
+                            Diagnostic::error(
+                                "argument-mismatch",
+                                format!("printf arguments in label {} differ between files", k),
+                            )
+                            .with_label(*k)
+                            .with_location(first.location(k).cloned()),
+                        );
                     }
                 }
             }
         }
     }
 
-    Ok(())
+    Ok(problems)
+}
+
+fn check_codepages<T>(file: T, codepages: &Codepages) -> Result<Vec<Diagnostic>, ParseError>
+where
+    T: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let ParsedData {
+        sections,
+        translation_locations,
+        errors,
+        warnings,
+        ..
+    } = parse_data_recovering(data).with_file(&file);
+    let mut problems: Vec<Diagnostic> = errors
+        .into_iter()
+        .map(Diagnostic::from)
+        .chain(warnings)
+        .collect();
+
+    for (s, locations) in sections.iter().zip(translation_locations) {
+        problems.extend(
+            s.check_translations_codepage(codepages)
+                .into_iter()
+                .map(|d| {
+                    let location = d.language.and_then(|l| locations.get(&l)).cloned();
+                    d.with_location(location)
+                }),
+        );
+    }
+
+    Ok(problems)
+}
+
+fn check_markup<T>(file: T, reference: Language) -> Result<Vec<Diagnostic>, ParseError>
+where
+    T: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let ParsedData {
+        sections,
+        locations,
+        errors,
+        warnings,
+        ..
+    } = parse_data_recovering(data).with_file(&file);
+    let mut problems: Vec<Diagnostic> = errors
+        .into_iter()
+        .map(Diagnostic::from)
+        .chain(warnings)
+        .collect();
+
+    for (s, location) in sections.iter().zip(locations) {
+        problems.extend(
+            s.check_translations_markup(reference)
+                .into_iter()
+                .map(|d| d.with_location(Some(location.clone()))),
+        );
+    }
+
+    Ok(problems)
 }
 
 fn find_incomplete<T>(
@@ -344,75 +1212,750 @@ fn find_incomplete<T>(
     second_file: Option<T>,
     save_file: T,
     locale_type: LocaleType,
-) -> Result<(), ParseError>
+    languages: &[Language],
+) -> Result<Vec<Diagnostic>, ParseError>
 where
     T: AsRef<Path>,
 {
-    let data = &*read_file(file.unwrap())?;
+    let file = file.unwrap();
+    let data = &*read_file(&file)?;
+    let mut problems = vec![];
     let occurrences: String = match locale_type {
         LocaleType::LocaleString => {
-            let parsed_data = parse_data(data)?;
+            let ParsedData {
+                sections,
+                locations,
+                errors,
+                warnings,
+                ..
+            } = parse_data_recovering(data).with_file(&file);
+            problems.extend(errors.into_iter().map(Diagnostic::from).chain(warnings));
+            let languages = match languages {
+                [] => discover_languages(&sections),
+                l => l.to_vec(),
+            };
+
+            for (s, location) in sections.iter().zip(locations) {
+                problems.extend(
+                    find_missing_translations(s, &languages)
+                        .into_iter()
+                        .map(|d| d.with_location(Some(location.clone()))),
+                );
+            }
 
-            find_incomplete_sections(parsed_data)
+            find_incomplete_sections(sections, &languages)
                 .iter()
                 .map(|s| format!("{}\n", *s))
                 .collect()
         }
 
         LocaleType::LocaleGameInterface => {
-            let second_file_data = &*read_file(second_file.unwrap())?;
+            let second_file = second_file.unwrap();
+            let second_file_data = &*read_file(&second_file)?;
 
-            find_missing_labels(data, second_file_data)?
+            let mut first = parse_clientside_recovering(data).with_file(&file);
+            let mut second = parse_clientside_recovering(second_file_data).with_file(&second_file);
+            problems.extend(
+                first
+                    .errors
+                    .drain(..)
+                    .chain(second.errors.drain(..))
+                    .map(Diagnostic::from),
+            );
+            let (map_first, map_second) = (first.map(), second.map());
+
+            let missing: Vec<Diagnostic> = find_missing_labels(&map_first, &map_second)
+                .into_iter()
+                .map(|d| {
+                    let location = d.label.as_deref().and_then(|l| first.location(l)).cloned();
+                    d.with_location(location)
+                })
+                .collect();
+            let occurrences = missing
                 .iter()
-                .map(|s| format!("{}\n", *s))
-                .collect()
+                .filter_map(|d| d.label.as_ref())
+                .map(|s| format!("{}\n", s))
+                .collect();
+            problems.extend(missing);
+
+            occurrences
         }
     };
 
-    let _ = std::fs::write(save_file, &occurrences)?;
+    std::fs::write(save_file, &occurrences)?;
+
+    Ok(problems)
+}
+
+fn stats<T>(
+    file: T,
+    second_file: Option<T>,
+    locale_type: LocaleType,
+    languages: &[Language],
+    format: ReportFormat,
+) -> Result<(), ParseError>
+where
+    T: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let stats = match locale_type {
+        LocaleType::LocaleString => {
+            let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+            let languages = match languages {
+                [] => discover_languages(&sections),
+                l => l.to_vec(),
+            };
+
+            Stats::new(&sections, &languages)
+        }
+        LocaleType::LocaleGameInterface => {
+            let second_file = second_file.unwrap();
+            let second_data = &*read_file(&second_file)?;
+            let first = parse_clientside_entries(data).map_err(|e| e.with_file(&file))?;
+            let second =
+                parse_clientside_entries(second_data).map_err(|e| e.with_file(&second_file))?;
+
+            // both files hold a single language, labels become sections with two translations
+            let source = languages.first().copied().unwrap_or(Language::PL);
+            let target = languages.get(1).copied().unwrap_or(Language::EN);
+            let sections = convert_clientside(vec![(source, first), (target, second)]);
+
+            Stats::new(&sections, &[source, target])
+        }
+    };
+
+    let rendered = stats.render(format).map_err(std::io::Error::from)?;
+    println!("{}", rendered.trim_end());
+
+    Ok(())
+}
+
+fn export_po<T, D>(file: T, out_dir: D, languages: &[Language]) -> Result<(), ParseError>
+where
+    T: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+    let document = Document::parse(data);
+    let languages = match languages {
+        [] => discover_languages(&sections),
+        l => l.to_vec(),
+    };
+    let source = languages.first().copied().unwrap_or(Language::PL);
+    let stem = file
+        .as_ref()
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "messages".to_string());
+
+    std::fs::create_dir_all(&out_dir)?;
+    std::fs::write(
+        out_dir.as_ref().join(format!("{}.pot", stem)),
+        generate_po(&sections, &document, source, None),
+    )?;
+    for lang in languages.iter().filter(|l| **l != source) {
+        std::fs::write(
+            out_dir
+                .as_ref()
+                .join(format!("{}.{}.po", stem, lang.code().to_ascii_lowercase())),
+            generate_po(&sections, &document, source, Some(*lang)),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn import_po<T, P>(
+    file: T,
+    po_files: &[P],
+    save_file: T,
+    strategy: MergeStrategy,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, PoError>
+where
+    T: AsRef<Path>,
+    P: AsRef<Path>,
+{
+    let mut po = vec![];
+    for po_file in po_files {
+        po.push(parse_po(&read_file(po_file)?).map_err(|e| e.with_file(po_file))?);
+    }
+    let imported = po.iter().flat_map(|p| p.sections()).collect();
+
+    Ok(import_sections(
+        file, imported, save_file, strategy, encoding,
+    )?)
+}
+
+fn export_xliff<T, D>(file: T, out_dir: D, languages: &[Language]) -> Result<(), ParseError>
+where
+    T: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+    let document = Document::parse(data);
+    let languages = match languages {
+        [] => discover_languages(&sections),
+        l => l.to_vec(),
+    };
+    let source = languages.first().copied().unwrap_or(Language::PL);
+    let original = file
+        .as_ref()
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    std::fs::create_dir_all(&out_dir)?;
+    for lang in languages.iter().filter(|l| **l != source) {
+        std::fs::write(
+            out_dir
+                .as_ref()
+                .join(format!("{}.xlf", lang.code().to_ascii_lowercase())),
+            generate_xliff(&sections, &document, source, *lang, &original),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn import_xliff<T, X>(
+    file: T,
+    xliff_files: &[X],
+    save_file: T,
+    strategy: MergeStrategy,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, XliffError>
+where
+    T: AsRef<Path>,
+    X: AsRef<Path>,
+{
+    let mut xliff = vec![];
+    for xliff_file in xliff_files {
+        xliff.push(parse_xliff(&read_file(xliff_file)?).map_err(|e| e.with_file(xliff_file))?);
+    }
+    let imported = xliff.iter().flat_map(|x| x.sections()).collect();
+
+    Ok(import_sections(
+        file, imported, save_file, strategy, encoding,
+    )?)
+}
+
+fn export_sheet<T>(
+    file: T,
+    save_file: T,
+    languages: &[Language],
+    format: Option<SheetFormat>,
+) -> Result<(), SheetError>
+where
+    T: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+    let languages = match languages {
+        [] => discover_languages(&sections),
+        l => l.to_vec(),
+    };
+    let format = format.unwrap_or_else(|| SheetFormat::from_path(&save_file));
+
+    std::fs::write(save_file, generate_sheet(&sections, &languages, format)?)?;
+
+    Ok(())
+}
+
+fn import_sheet<T>(
+    file: T,
+    sheet_file: T,
+    save_file: T,
+    strategy: MergeStrategy,
+    format: Option<SheetFormat>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, SheetError>
+where
+    T: AsRef<Path>,
+{
+    let format = format.unwrap_or_else(|| SheetFormat::from_path(&sheet_file));
+    let rows =
+        parse_sheet(&read_file(&sheet_file)?, format).map_err(|e| e.with_file(&sheet_file))?;
+
+    let (data, _) = read_encoded(&file, encoding)?;
+    let sections = parse_data(&data).map_err(|e| e.with_file(&file))?;
+    let (imported, mut problems) = sheet_sections(&rows, &sections);
+    for d in &mut problems {
+        if let Some(location) = d.location.as_mut() {
+            location.set_file(&sheet_file);
+        }
+    }
+
+    problems.extend(import_sections(
+        file, imported, save_file, strategy, encoding,
+    )?);
+
+    Ok(problems)
+}
+
+/// Merges the imported sections into the file and saves the result. Imported translations
+/// with a tab or line break are left out and reported. Nothing is saved when the merged
+/// translations take different printf arguments.
+fn import_sections<T>(
+    file: T,
+    imported: Vec<Section>,
+    save_file: T,
+    strategy: MergeStrategy,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, ParseError>
+where
+    T: AsRef<Path>,
+{
+    let (data, file_encoding) = read_encoded(&file, encoding)?;
+    let data = &*data;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+
+    let mut problems = vec![];
+    let mut accepted = vec![];
+    for section in imported {
+        let unsupported = section.unsupported_characters();
+        if unsupported.is_empty() {
+            accepted.push(section);
+        } else {
+            problems.extend(unsupported);
+        }
+    }
+    let merged = merge_sections(sections, accepted, strategy);
+
+    let mismatches = argument_mismatches(&merged);
+    if !mismatches.is_empty() {
+        problems.extend(mismatches);
+        return Ok(problems);
+    }
+
+    let mut document = Document::parse(data);
+    document.apply(&merged);
+
+    write_encoded(save_file, &document.generate(), file_encoding)?;
+
+    Ok(problems)
+}
+
+fn dump<T>(file: T, save_file: T, format: Option<ModelFormat>) -> Result<(), ModelError>
+where
+    T: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+    let format = format.unwrap_or_else(|| ModelFormat::from_path(&save_file));
+
+    std::fs::write(save_file, serialize_sections(&sections, format)?)?;
 
     Ok(())
 }
 
-fn convert<T>(old_file: T, save_file: T, lang: Language) -> Result<(), ConvertError>
+fn load<T>(
+    file: T,
+    save_file: T,
+    format: Option<ModelFormat>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, ModelError>
 where
     T: AsRef<Path>,
 {
-    let old_data = &*read_file(old_file)?;
-    let converted_data = convert_data(old_data, lang)?;
-    let generated: String = converted_data
+    let format = format.unwrap_or_else(|| ModelFormat::from_path(&file));
+    let map = deserialize_sections(&read_file(&file)?, format).map_err(|e| e.with_file(&file))?;
+    let sections = map.sections();
+
+    write_encoded(
+        save_file,
+        &generate_file(&sections),
+        encoding.unwrap_or(UTF_8),
+    )?;
+
+    Ok(argument_mismatches(&sections))
+}
+
+/// Errors for every translation taking other printf arguments than the rest of its section.
+fn argument_mismatches(sections: &[Section]) -> Vec<Diagnostic> {
+    sections
         .iter()
-        .map(|f| format!("{}\n\n", f.generate()))
-        .collect();
+        .flat_map(|s| s.argument_mismatches())
+        .collect()
+}
+
+/// File made of the generated sections separated by blank lines.
+fn generate_file(sections: &[Section]) -> String {
+    sections
+        .iter()
+        .map(|s| format!("{}\n\n", s.generate()))
+        .collect()
+}
+
+fn export_tmx<T>(file: T, save_file: T, languages: &[Language]) -> Result<(), ParseError>
+where
+    T: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+    let languages = match languages {
+        [] => discover_languages(&sections),
+        l => l.to_vec(),
+    };
 
-    let _ = std::fs::write(save_file, &generated)?;
+    std::fs::write(save_file, generate_tmx(&sections, &languages))?;
 
     Ok(())
 }
 
-fn merge<T>(cur_file: T, new_file: T, save_file: T) -> Result<(), ParseError>
+fn client_to_server<S>(
+    files: &[(Language, PathBuf)],
+    save_file: S,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, ParseError>
+where
+    S: AsRef<Path>,
+{
+    let mut data = vec![];
+    for (_, file) in files {
+        data.push(read_file(file)?);
+    }
+
+    let mut inputs = vec![];
+    let mut problems = vec![];
+    for ((lang, file), data) in files.iter().zip(data.iter()) {
+        let mut parsed = parse_clientside_recovering(data).with_file(file);
+        if !parsed.errors.is_empty() {
+            return Err(parsed.errors.remove(0));
+        }
+
+        let mut entries = vec![];
+        for ((label, text), location) in parsed.entries.into_iter().zip(parsed.locations) {
+            let unsupported = borrowed_section(label, [(*lang, text)]).unsupported_characters();
+            if unsupported.is_empty() {
+                entries.push((label, text));
+            } else {
+                problems.extend(
+                    unsupported
+                        .into_iter()
+                        .map(|d| d.with_location(Some(location.clone()))),
+                );
+            }
+        }
+        inputs.push((*lang, entries));
+    }
+    let sections = convert_clientside(inputs);
+
+    write_encoded(
+        save_file,
+        &generate_file(&sections),
+        encoding.unwrap_or(UTF_8),
+    )?;
+
+    problems.extend(argument_mismatches(&sections));
+    Ok(problems)
+}
+
+fn server_to_client<T, D>(
+    file: T,
+    out_dir: D,
+    name: &str,
+    languages: &[Language],
+    codepages: &Codepages,
+) -> Result<(), ParseError>
+where
+    T: AsRef<Path>,
+    D: AsRef<Path>,
+{
+    let data = &*read_file(&file)?;
+    let sections = parse_data(data).map_err(|e| e.with_file(&file))?;
+    let languages = match languages {
+        [] => discover_languages(&sections),
+        l => l.to_vec(),
+    };
+
+    for lang in languages {
+        let dir = out_dir.as_ref().join(lang.code().to_ascii_lowercase());
+        std::fs::create_dir_all(&dir)?;
+        write_encoded(
+            dir.join(name),
+            &generate_clientside(&sections, lang),
+            codepages.get(lang),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn convert<T>(
+    old_file: T,
+    save_file: T,
+    lang: Language,
+    encoding: Option<&'static Encoding>,
+) -> Result<(), ConvertError>
+where
+    T: AsRef<Path>,
+{
+    let (old_data, encoding) = read_encoded(&old_file, encoding)?;
+    let old_data = &*old_data;
+    let converted_data = convert_data(old_data, lang).map_err(|e| e.with_file(&old_file))?;
+
+    write_encoded(save_file, &generate_file(&converted_data), encoding)?;
+
+    Ok(())
+}
+
+/// Merges the newer file into the current one. Nothing is saved when the merged translations
+/// take different printf arguments.
+fn merge<T>(
+    cur_file: T,
+    new_file: T,
+    save_file: T,
+    strategy: MergeStrategy,
+    report_format: Option<ReportFormat>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, ParseError>
+where
+    T: AsRef<Path>,
+{
+    let (cur_data, cur_encoding) = read_encoded(&cur_file, encoding)?;
+    let (new_data, _) = read_encoded(&new_file, encoding)?;
+    let (cur_data, new_data) = (&*cur_data, &*new_data);
+
+    let cur_sections = parse_data(cur_data).map_err(|e| e.with_file(&cur_file))?;
+    let new_sections = parse_data(new_data).map_err(|e| e.with_file(&new_file))?;
+    let merged = merge_sections(cur_sections.clone(), new_sections, strategy);
+
+    let mismatches = argument_mismatches(&merged);
+    if !mismatches.is_empty() {
+        return Ok(mismatches);
+    }
+
+    // only lines which changed are rewritten, comments and formatting stay as they were
+    let mut document = Document::parse(cur_data);
+    document.apply(&merged);
+
+    write_encoded(save_file, &document.generate(), cur_encoding)?;
+    print_merge_report(&cur_sections, &merged, report_format)?;
+
+    Ok(vec![])
+}
+
+/// Merges the newer client file into the current one. Nothing is saved when a merged text takes
+/// different printf arguments than the current one.
+fn merge_clientside_files<T>(
+    cur_file: T,
+    new_file: T,
+    save_file: T,
+    strategy: MergeStrategy,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, MergeError>
 where
     T: AsRef<Path>,
 {
-    let cur_data = &*read_file(cur_file)?;
-    let new_data = &*read_file(new_file)?;
+    let (cur_data, cur_encoding) = read_encoded(&cur_file, encoding)?;
+    let (new_data, _) = read_encoded(&new_file, encoding)?;
+    let (cur_data, new_data) = (&*cur_data, &*new_data);
 
-    let cur_sections = parse_data(cur_data)?;
-    let new_sections = parse_data(new_data)?;
-    let merged = merge_sections(cur_sections, new_sections);
+    let cur_entries = parse_clientside_entries(cur_data).map_err(|e| e.with_file(&cur_file))?;
+    let new_entries = parse_clientside_entries(new_data).map_err(|e| e.with_file(&new_file))?;
+    let merged = merge_clientside(cur_entries.clone(), new_entries, strategy)?;
 
-    for s in &merged {
-        if !s.check_translations_arguments() {
-            return Err(ParseError::ArgumentMismatch(s.label.to_string()));
+    let mut mismatches = vec![];
+    for (label, text) in &merged {
+        if let Some((_, old)) = cur_entries.iter().find(|(l, _)| l == label) {
+            if !check_string_arguments(old, text) {
+                mismatches.push(
+                    Diagnostic::error(
+                        "argument-mismatch",
+                        format!(
+                            "label {} takes arguments {} but the current text takes {}",
+                            label,
+                            format_argument_types(&argument_types(text)),
+                            format_argument_types(&argument_types(old))
+                        ),
+                    )
+                    .with_label(*label),
+                );
+            }
         }
     }
+    if !mismatches.is_empty() {
+        return Ok(mismatches);
+    }
 
     let generated: String = merged
         .iter()
-        .map(|f| format!("{}\n\n", f.generate()))
+        .map(|(label, text)| format!("{}\t{}\n", label, text))
         .collect();
 
-    let _ = std::fs::write(save_file, &generated)?;
+    write_encoded(save_file, &generated, cur_encoding)?;
+
+    Ok(vec![])
+}
+
+fn ask_conflict_resolution<'a>(
+    theme: &ColorfulTheme,
+    conflict: &Conflict<'a>,
+) -> std::io::Result<Option<&'a str>> {
+    let describe = |side: &str, text: Option<&str>| match text {
+        Some(text) => format!("{}: \"{}\"", side, text),
+        None => format!("{}: no translation", side),
+    };
+    let items = vec![
+        describe("ours", conflict.ours),
+        describe("theirs", conflict.theirs),
+        describe("base", conflict.base),
+    ];
+
+    let choice = Select::with_theme(theme)
+        .with_prompt(format!(
+            "Conflict in label {} ({}), choose translation:",
+            conflict.label, conflict.language
+        ))
+        .items(&items)
+        .default(0)
+        .interact()?;
+
+    Ok(match choice {
+        0 => conflict.ours,
+        1 => conflict.theirs,
+        _ => conflict.base,
+    })
+}
+
+/// Three-way merge of files. Without `resolution` every conflict is resolved by asking the user.
+/// Nothing is saved when the merged translations take different printf arguments.
+fn merge_three_way_files<T>(
+    base_file: T,
+    ours_file: T,
+    theirs_file: T,
+    save_file: T,
+    resolution: Option<ConflictResolution>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, ParseError>
+where
+    T: AsRef<Path>,
+{
+    let (base_data, _) = read_encoded(&base_file, encoding)?;
+    let (ours_data, ours_encoding) = read_encoded(&ours_file, encoding)?;
+    let (theirs_data, _) = read_encoded(&theirs_file, encoding)?;
+    let (base_data, ours_data, theirs_data) = (&*base_data, &*ours_data, &*theirs_data);
+
+    let base_sections = parse_data(base_data).map_err(|e| e.with_file(&base_file))?;
+    let ours_sections = parse_data(ours_data).map_err(|e| e.with_file(&ours_file))?;
+    let theirs_sections = parse_data(theirs_data).map_err(|e| e.with_file(&theirs_file))?;
+
+    let ThreeWayMerge {
+        mut sections,
+        conflicts,
+    } = merge_three_way(&base_sections, &ours_sections, &theirs_sections);
+
+    let mut problems = vec![];
+    for c in &conflicts {
+        match resolution {
+            Some(ConflictResolution::Markers) | Some(ConflictResolution::Ours) => {}
+            Some(ConflictResolution::Theirs) => resolve_conflict(&mut sections, c, c.theirs),
+            None => {
+                let text = ask_conflict_resolution(&ColorfulTheme::default(), c)?;
+                resolve_conflict(&mut sections, c, text);
+                continue;
+            }
+        }
+        problems.push(c.diagnostic());
+    }
+    if resolution != Some(ConflictResolution::Markers) {
+        // labels we deleted stay deleted unless a translation of theirs was chosen
+        sections.retain(|s| {
+            !s.translations.is_empty() || ours_sections.iter().any(|o| o.label == s.label)
+        });
+    }
+
+    let mismatches = argument_mismatches(&sections);
+    if !mismatches.is_empty() {
+        problems.extend(mismatches);
+        return Ok(problems);
+    }
+
+    let generated: String = if resolution == Some(ConflictResolution::Markers) {
+        generate_with_conflicts(&sections, &conflicts)
+    } else {
+        let mut document = Document::parse(ours_data);
+        document.apply(&sections);
+        document.generate()
+    };
+
+    write_encoded(save_file, &generated, ours_encoding)?;
+
+    Ok(problems)
+}
+
+/// Merges all files in order of precedence, recording the source of every translation. Nothing
+/// is saved when the merged translations take different printf arguments.
+fn merge_files<T, S>(
+    files: &[T],
+    save_file: S,
+    strategy: MergeStrategy,
+    report_format: Option<ReportFormat>,
+    encoding: Option<&'static Encoding>,
+) -> Result<Vec<Diagnostic>, ParseError>
+where
+    T: AsRef<Path>,
+    S: AsRef<Path>,
+{
+    let mut data = vec![];
+    let mut encodings = vec![];
+    for file in files {
+        let (text, encoding) = read_encoded(file, encoding)?;
+        data.push(text);
+        encodings.push(encoding);
+    }
+
+    let mut inputs = vec![];
+    for (file, data) in files.iter().zip(data.iter()) {
+        inputs.push(parse_data(data).map_err(|e| e.with_file(file))?);
+    }
+
+    let base = inputs.first().cloned().unwrap_or_default();
+    let (merged, sources) = merge_many(inputs, strategy);
+
+    let mismatches = argument_mismatches(&merged);
+    if !mismatches.is_empty() {
+        return Ok(mismatches);
+    }
+
+    let names: Vec<String> = files
+        .iter()
+        .map(|f| f.as_ref().display().to_string())
+        .collect();
+    let comments: HashMap<&str, String> = merged
+        .iter()
+        .filter_map(|s| Some((s.label, sources_comment(s, &sources, &names)?)))
+        .collect();
+
+    // the output keeps the layout and the encoding of the base input
+    let mut document = Document::parse(data.first().map_or("", |d| d.as_str()));
+    document.apply(&merged);
+    document.set_comments(SOURCES_COMMENT, &comments);
+
+    write_encoded(
+        save_file,
+        &document.generate(),
+        encodings.first().copied().unwrap_or_else(|| UTF_8.into()),
+    )?;
+    print_merge_report(&base, &merged, report_format)?;
+
+    Ok(vec![])
+}
+
+fn print_merge_report(
+    base: &[Section],
+    merged: &[Section],
+    format: Option<ReportFormat>,
+) -> std::io::Result<()> {
+    if let Some(format) = format {
+        let rendered = MergeReport::new(base, merged)
+            .render(format)
+            .map_err(std::io::Error::from)?;
+        println!("{}", rendered.trim_end());
+    }
 
     Ok(())
 }
@@ -1,46 +1,87 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Located, Location};
+use crate::parse::ParseError;
 use crate::section::{Language, Section};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum ConvertError {
     #[error("file is empty")]
-    Empty,
+    Empty(Location),
     #[error("lines count {0} is not divisible by 2")]
-    Syntax(usize),
+    Syntax(usize, Location),
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
 
-pub fn convert_data<'a, T: ?Sized>(
-    data: &'a T,
-    lang: Language,
-) -> Result<Vec<Section<'a>>, ConvertError>
+impl ConvertError {
+    /// Sets the name of the file the error comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        match &mut self {
+            ConvertError::Empty(l) | ConvertError::Syntax(_, l) => l.set_file(file),
+            ConvertError::Io(_) => {}
+        }
+
+        self
+    }
+}
+
+impl Located for ConvertError {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            ConvertError::Empty(l) | ConvertError::Syntax(_, l) => Some(l),
+            ConvertError::Io(_) => None,
+        }
+    }
+}
+
+impl From<ConvertError> for Diagnostic {
+    fn from(error: ConvertError) -> Self {
+        let location = error.location().cloned();
+        let code = match error {
+            ConvertError::Empty(_) => "empty-file",
+            ConvertError::Syntax(..) => "odd-line-count",
+            ConvertError::Io(e) => return ParseError::Io(e).into(),
+        };
+
+        Diagnostic::error(code, error.to_string())
+            .with_location(location)
+            .with_kind(DiagnosticKind::Parse)
+    }
+}
+
+pub fn convert_data<'a, T>(data: &'a T, lang: Language) -> Result<Vec<Section<'a>>, ConvertError>
 where
-    T: AsRef<str>,
+    T: AsRef<str> + ?Sized,
 {
-    let lines: Vec<&str> = data
+    let lines: Vec<(usize, &str, &str)> = data
         .as_ref()
         .lines()
-        .map(|l| l.trim().trim_matches(|c| c == '\"' || c == ';'))
-        .filter(|l| !l.is_empty() && !l.starts_with("#"))
+        .enumerate()
+        .map(|(i, raw)| (i, raw, raw.trim().trim_matches(|c| c == '\"' || c == ';')))
+        .filter(|(_, _, l)| !l.is_empty() && !l.starts_with("#"))
         .collect();
 
     if lines.is_empty() {
-        return Err(ConvertError::Empty);
+        return Err(ConvertError::Empty(Location::in_line(0, "", "")));
     }
 
     if lines.len() % 2 != 0 {
-        return Err(ConvertError::Syntax(lines.len()));
+        let (i, raw, last) = lines[lines.len() - 1];
+        return Err(ConvertError::Syntax(
+            lines.len(),
+            Location::in_line(i, raw, last),
+        ));
     }
 
     let mut sections = vec![];
     for line in lines.chunks(2) {
         let mut translations = BTreeMap::new();
-        translations.insert(lang, line[1].as_ref());
+        translations.insert(lang, line[1].2);
 
         sections.push(Section {
-            label: line[0],
+            label: line[0].2,
             translations,
         })
     }
@@ -48,10 +89,52 @@ where
     Ok(sections)
 }
 
+/// Combines client-side files, each holding the translations of a single language, into
+/// sections. Labels keep the order in which they first appear.
+pub fn convert_clientside<'a>(files: Vec<(Language, Vec<(&'a str, &'a str)>)>) -> Vec<Section<'a>> {
+    let mut sections: Vec<Section> = vec![];
+
+    for (lang, entries) in files {
+        for (label, text) in entries {
+            let index = match sections.iter().position(|s| s.label == label) {
+                Some(i) => i,
+                None => {
+                    let mut section = Section::new();
+                    section.label = label;
+                    sections.push(section);
+                    sections.len() - 1
+                }
+            };
+            sections[index].translations.insert(lang, text);
+        }
+    }
+
+    sections
+}
+
+/// Parses a client-side file of a language such as `EN=locale_game_en.txt`.
+pub fn parse_language_file(assignment: &str) -> Result<(Language, PathBuf), String> {
+    let (lang, file) = assignment
+        .split_once('=')
+        .ok_or_else(|| format!("expected LANGUAGE=FILE, got {}", assignment))?;
+    Ok((lang.parse()?, PathBuf::from(file)))
+}
+
+/// Client-side file of the labels translated to the language.
+pub fn generate_clientside(sections: &[Section], lang: Language) -> String {
+    sections
+        .iter()
+        .filter_map(|s| Some(format!("{}\t{}\n", s.label, s.translations.get(&lang)?)))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::convert::convert_data;
-    use crate::section::Language::PL;
+    use crate::convert::{
+        convert_clientside, convert_data, generate_clientside, parse_language_file,
+    };
+    use crate::diagnostic::Located;
+    use crate::section::Language;
     use crate::section::Section;
 
     #[test]
@@ -62,9 +145,9 @@ mod tests {
 
         let mut section = Section::new();
         section.label = "hello";
-        section.translations.insert(PL, "world");
+        section.translations.insert(Language::PL, "world");
 
-        let convert = convert_data(s, PL);
+        let convert = convert_data(s, Language::PL);
 
         assert!(convert.is_ok());
         assert_eq!(convert.unwrap(), vec![section]);
@@ -75,15 +158,64 @@ mod tests {
         let s = "\
 \"hello\";";
 
-        let convert = convert_data(s, PL);
+        let convert = convert_data(s, Language::PL);
         assert!(convert.is_err());
     }
 
+    #[test]
+    fn error_points_at_unpaired_line() {
+        let s = "\
+\"hello\";\n\
+\"world\";\n\
+\n\
+\t\"unpaired\";";
+
+        let location = convert_data(s, Language::PL)
+            .unwrap_err()
+            .location()
+            .cloned()
+            .unwrap();
+        assert_eq!((location.line, location.column), (4, 3));
+    }
+
     #[test]
     fn error_if_empty() {
         let s = "";
 
-        let convert = convert_data(s, PL);
+        let convert = convert_data(s, Language::PL);
         assert!(convert.is_err());
     }
+
+    #[test]
+    fn converts_clientside_files_to_sections_and_back() {
+        let pl = vec![("sword", "Miecz"), ("shield", "Tarcza")];
+        let en = vec![("bow", "Bow"), ("sword", "Sword")];
+
+        let sections = convert_clientside(vec![(Language::PL, pl), (Language::EN, en)]);
+
+        let labels: Vec<&str> = sections.iter().map(|s| s.label).collect();
+        assert_eq!(labels, vec!["sword", "shield", "bow"]);
+        assert_eq!(sections[0].translations[&Language::PL], "Miecz");
+        assert_eq!(sections[0].translations[&Language::EN], "Sword");
+        assert_eq!(sections[1].translations.len(), 1);
+
+        assert_eq!(
+            generate_clientside(&sections, Language::EN),
+            "sword\tSword\nbow\tBow\n"
+        );
+        assert_eq!(
+            generate_clientside(&sections, Language::PL),
+            "sword\tMiecz\nshield\tTarcza\n"
+        );
+    }
+
+    #[test]
+    fn parses_language_files() {
+        assert_eq!(
+            parse_language_file("en=client/locale_game.txt"),
+            Ok((Language::EN, "client/locale_game.txt".into()))
+        );
+        assert!(parse_language_file("locale_game.txt").is_err());
+        assert!(parse_language_file("e1=locale_game.txt").is_err());
+    }
 }
@@ -0,0 +1,607 @@
+use crate::parse::read_file;
+use crate::section::Language;
+use console::style;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Position of a problem in a file. Lines and columns start at 1.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Location {
+    /// Location of the trimmed `text` inside the 0-based `line_index` line of `raw_line`.
+    pub fn in_line(line_index: usize, raw_line: &str, text: &str) -> Self {
+        let offset = match raw_line.find(text) {
+            Some(offset) => raw_line[..offset].chars().count(),
+            None => 0,
+        };
+
+        Location {
+            file: String::new(),
+            line: line_index + 1,
+            column: offset + 1,
+            length: text.chars().count().max(1),
+        }
+    }
+
+    pub fn set_file<T: AsRef<Path>>(&mut self, file: T) {
+        self.file = file.as_ref().display().to_string();
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let file = if self.file.is_empty() {
+            "<input>"
+        } else {
+            &self.file
+        };
+        write!(f, "{}:{}:{}", file, self.line, self.column)
+    }
+}
+
+/// Errors which can point at the place in a file where they occurred.
+pub trait Located {
+    fn location(&self) -> Option<&Location>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warning" => Ok(Severity::Warning),
+            "error" => Ok(Severity::Error),
+            s => Err(format!("invalid severity {}, expected warning or error", s)),
+        }
+    }
+}
+
+/// What the diagnostic means for the outcome of the command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// Problem found in otherwise valid input, e.g. by a check.
+    Finding,
+    /// Input could not be parsed or processed.
+    Parse,
+    /// File could not be read or written.
+    Io,
+    /// Options which cannot be used together.
+    Usage,
+}
+
+/// Process exit codes, ordered from the least to the most severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExitCode {
+    Success = 0,
+    Findings = 1,
+    ParseError = 2,
+    IoError = 3,
+    /// Invalid command line, as `EX_USAGE` of sysexits.h.
+    Usage = 64,
+}
+
+/// Exit code for the diagnostics. Findings below the `fail_on` severity do not fail the process,
+/// parse and I/O errors always do.
+pub fn exit_code(diagnostics: &[Diagnostic], fail_on: Severity) -> ExitCode {
+    diagnostics
+        .iter()
+        .map(|d| match d.kind {
+            DiagnosticKind::Usage => ExitCode::Usage,
+            DiagnosticKind::Io => ExitCode::IoError,
+            DiagnosticKind::Parse => ExitCode::ParseError,
+            DiagnosticKind::Finding if d.severity >= fail_on => ExitCode::Findings,
+            DiagnosticKind::Finding => ExitCode::Success,
+        })
+        .max()
+        .unwrap_or(ExitCode::Success)
+}
+
+/// Problem found by a parser or a check, e.g. a syntax error or a missing translation.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    /// Stable identifier of the kind of problem, e.g. `argument-mismatch`.
+    pub code: &'static str,
+    pub severity: Severity,
+    pub label: Option<String>,
+    pub language: Option<Language>,
+    pub location: Option<Location>,
+    pub message: String,
+    #[serde(skip)]
+    pub kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    pub fn new<T: Into<String>>(code: &'static str, severity: Severity, message: T) -> Self {
+        Diagnostic {
+            code,
+            severity,
+            label: None,
+            language: None,
+            location: None,
+            message: message.into(),
+            kind: DiagnosticKind::Finding,
+        }
+    }
+
+    pub fn error<T: Into<String>>(code: &'static str, message: T) -> Self {
+        Diagnostic::new(code, Severity::Error, message)
+    }
+
+    pub fn warning<T: Into<String>>(code: &'static str, message: T) -> Self {
+        Diagnostic::new(code, Severity::Warning, message)
+    }
+
+    pub fn with_label<T: Into<String>>(mut self, label: T) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub fn with_location(mut self, location: Option<Location>) -> Self {
+        self.location = location;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: DiagnosticKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// Renders the diagnostic the way compilers do, with the offending line of `source`, the
+/// content of the file it comes from, and a caret under the span.
+pub fn render_diagnostic(diagnostic: &Diagnostic, source: Option<&str>) -> String {
+    let severity = match diagnostic.severity {
+        Severity::Error => style(format!("error[{}]", diagnostic.code)).red().bold(),
+        Severity::Warning => style(format!("warning[{}]", diagnostic.code))
+            .yellow()
+            .bold(),
+    };
+    let mut out = format!("{}: {}\n", severity, style(&diagnostic.message).bold());
+    let location = match &diagnostic.location {
+        Some(l) => l,
+        None => return out,
+    };
+
+    let line_no = location.line.to_string();
+    let gutter = " ".repeat(line_no.len());
+    let bar = style("|").blue().bold();
+    out.push_str(&format!(
+        "{}{} {}\n",
+        gutter,
+        style("-->").blue().bold(),
+        location
+    ));
+
+    let line = match source.and_then(|s| s.lines().nth(location.line.saturating_sub(1))) {
+        Some(l) => l,
+        None => return out,
+    };
+
+    // keep tabs in the padding so the caret lines up with the source line
+    let padding: String = line
+        .chars()
+        .take(location.column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+    let carets = "^".repeat(location.length.max(1));
+    let carets = match diagnostic.severity {
+        Severity::Error => style(carets).red().bold(),
+        Severity::Warning => style(carets).yellow().bold(),
+    };
+
+    out.push_str(&format!("{} {}\n", gutter, bar));
+    out.push_str(&format!(
+        "{} {} {}\n",
+        style(&line_no).blue().bold(),
+        bar,
+        line
+    ));
+    out.push_str(&format!("{} {} {}{}\n", gutter, bar, padding, carets));
+
+    out
+}
+
+/// Renders diagnostics grouped by label, followed by a summary of their counts.
+pub fn render_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    if diagnostics.is_empty() {
+        return String::new();
+    }
+
+    let mut labels: Vec<Option<&str>> = vec![];
+    for d in diagnostics {
+        if !labels.contains(&d.label.as_deref()) {
+            labels.push(d.label.as_deref());
+        }
+    }
+    // problems not tied to a label, like syntax errors, come first
+    labels.sort_by_key(|l| l.is_some());
+
+    // every file is read once, no matter how many diagnostics point into it
+    let mut sources: BTreeMap<&str, Option<String>> = BTreeMap::new();
+    for l in diagnostics.iter().filter_map(|d| d.location.as_ref()) {
+        sources
+            .entry(l.file.as_str())
+            .or_insert_with(|| read_file(&l.file).ok());
+    }
+
+    let mut out = String::new();
+    for label in labels {
+        if let Some(label) = label {
+            out.push_str(&format!(
+                "{}\n",
+                style(format!("label {}", label)).cyan().bold()
+            ));
+        }
+        for d in diagnostics.iter().filter(|d| d.label.as_deref() == label) {
+            let source = d
+                .location
+                .as_ref()
+                .and_then(|l| sources.get(l.file.as_str())?.as_deref());
+            out.push_str(&render_diagnostic(d, source));
+        }
+        out.push('\n');
+    }
+
+    let count = |severity| {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == severity)
+            .count()
+    };
+    out.push_str(&format!(
+        "{} error(s), {} warning(s)\n",
+        count(Severity::Error),
+        count(Severity::Warning)
+    ));
+
+    out
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "sarif" => Ok(OutputFormat::Sarif),
+            f => Err(format!(
+                "invalid output format {}, expected text, json or sarif",
+                f
+            )),
+        }
+    }
+}
+
+/// Renders diagnostics in the given format.
+pub fn render_as(diagnostics: &[Diagnostic], format: OutputFormat) -> serde_json::Result<String> {
+    match format {
+        OutputFormat::Text => Ok(render_diagnostics(diagnostics)),
+        OutputFormat::Json => serde_json::to_string_pretty(diagnostics).map(|s| s + "\n"),
+        OutputFormat::Sarif => render_sarif(diagnostics).map(|s| s + "\n"),
+    }
+}
+
+/// Percent-encodes every byte of the path except unreserved characters and separators.
+fn percent_encode(path: &str, keep_colon: bool) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            b':' if keep_colon => ":".to_string(),
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// URI of a file path, relative paths stay relative to the working directory.
+fn file_uri(file: &str) -> String {
+    let path = file.replace('\\', "/");
+    if Path::new(file).is_absolute() {
+        format!(
+            "file://{}{}",
+            if path.starts_with('/') { "" } else { "/" },
+            percent_encode(&path, true)
+        )
+    } else {
+        percent_encode(&path, false)
+    }
+}
+
+/// Renders diagnostics as a SARIF 2.1.0 log with a single run.
+pub fn render_sarif(diagnostics: &[Diagnostic]) -> serde_json::Result<String> {
+    let mut rules: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let results: Vec<_> = diagnostics
+        .iter()
+        .map(|d| {
+            let mut result = json!({
+                "ruleId": d.code,
+                "level": d.severity,
+                "message": { "text": d.message },
+                "properties": { "label": d.label, "language": d.language },
+            });
+            if let Some(l) = &d.location {
+                result["locations"] = json!([{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": file_uri(&l.file) },
+                        "region": {
+                            "startLine": l.line,
+                            "startColumn": l.column,
+                            "endColumn": l.column + l.length,
+                        },
+                    },
+                }]);
+            }
+            result
+        })
+        .collect();
+
+    serde_json::to_string_pretty(&json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules.iter().map(|r| json!({ "id": r })).collect::<Vec<_>>(),
+                },
+            },
+            "results": results,
+        }],
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{
+        exit_code, file_uri, render_as, render_diagnostic, render_diagnostics, Diagnostic,
+        DiagnosticKind, ExitCode, Location, OutputFormat, Severity,
+    };
+    use crate::merge::Conflict;
+    use crate::po::PoError;
+    use crate::section::{borrowed_section, Language};
+    use console::strip_ansi_codes;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn locates_text_in_line() {
+        let location = Location::in_line(3, "\tPL\t\"pl2\"", "PL");
+
+        assert_eq!(location.line, 4);
+        assert_eq!(location.column, 2);
+        assert_eq!(location.length, 2);
+    }
+
+    #[test]
+    fn renders_caret_under_span() {
+        let location = Location {
+            file: "Cargo.toml".to_owned(),
+            line: 3,
+            column: 11,
+            length: 7,
+        };
+        let diagnostic = Diagnostic::error("broken", "broken").with_location(Some(location));
+
+        let source = "[package]\nname = \"localestring-merger\"\nversion = \"0.1.0\"\n";
+        let rendered = render_diagnostic(&diagnostic, Some(source));
+        let rendered = strip_ansi_codes(&rendered);
+
+        assert!(rendered.starts_with("error[broken]: broken\n --> Cargo.toml:3:11\n"));
+        assert!(rendered.ends_with("3 | version = \"0.1.0\"\n  |           ^^^^^^^\n"));
+    }
+
+    #[test]
+    fn groups_diagnostics_by_label() {
+        let diagnostics = vec![
+            Diagnostic::warning("missing-translation", "no EN")
+                .with_label("a")
+                .with_language(Language::EN),
+            Diagnostic::error("syntax", "bad line"),
+            Diagnostic::error("argument-mismatch", "arguments differ").with_label("b"),
+            Diagnostic::warning("missing-translation", "no DE")
+                .with_label("a")
+                .with_language(Language::DE),
+        ];
+
+        let rendered = render_diagnostics(&diagnostics);
+
+        assert_eq!(
+            strip_ansi_codes(&rendered),
+            "error[syntax]: bad line\n\
+            \n\
+            label a\n\
+            warning[missing-translation]: no EN\n\
+            warning[missing-translation]: no DE\n\
+            \n\
+            label b\n\
+            error[argument-mismatch]: arguments differ\n\
+            \n\
+            2 error(s), 2 warning(s)\n"
+        );
+        assert!(render_diagnostics(&[]).is_empty());
+    }
+
+    #[test]
+    fn renders_source_lines_of_each_diagnostic() {
+        let at_line = |line| {
+            Diagnostic::error("broken", "broken").with_location(Some(Location {
+                file: "Cargo.toml".to_owned(),
+                line,
+                column: 1,
+                length: 1,
+            }))
+        };
+
+        let rendered = render_diagnostics(&[at_line(1), at_line(3), at_line(99)]);
+        let rendered = strip_ansi_codes(&rendered);
+
+        assert!(rendered.contains("1 | [package]\n"));
+        assert!(rendered.contains("3 | version = \"0.1.0\"\n"));
+        assert!(rendered.contains(" --> Cargo.toml:99:1\n"));
+    }
+
+    fn located_diagnostic() -> Diagnostic {
+        Diagnostic::warning("missing-translation", "label a has no translation in EN")
+            .with_label("a")
+            .with_language(Language::EN)
+            .with_location(Some(Location {
+                file: "locale_string.txt".to_owned(),
+                line: 2,
+                column: 6,
+                length: 1,
+            }))
+    }
+
+    #[test]
+    fn renders_json() {
+        let rendered = render_as(&[located_diagnostic()], OutputFormat::Json).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<Value>(&rendered).unwrap(),
+            json!([{
+                "code": "missing-translation",
+                "severity": "warning",
+                "label": "a",
+                "language": "EN",
+                "location": { "file": "locale_string.txt", "line": 2, "column": 6, "length": 1 },
+                "message": "label a has no translation in EN",
+            }])
+        );
+    }
+
+    #[test]
+    fn renders_sarif() {
+        let diagnostics = vec![
+            located_diagnostic(),
+            Diagnostic::error("io", "file not found"),
+        ];
+        let rendered = render_as(&diagnostics, OutputFormat::Sarif).unwrap();
+        let sarif: Value = serde_json::from_str(&rendered).unwrap();
+
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            run["tool"]["driver"]["rules"],
+            json!([{ "id": "io" }, { "id": "missing-translation" }])
+        );
+        assert_eq!(run["results"][0]["level"], "warning");
+        assert_eq!(
+            run["results"][0]["locations"][0]["physicalLocation"],
+            json!({
+                "artifactLocation": { "uri": "locale_string.txt" },
+                "region": { "startLine": 2, "startColumn": 6, "endColumn": 7 },
+            })
+        );
+        assert_eq!(run["results"][1]["level"], "error");
+        assert!(run["results"][1].get("locations").is_none());
+    }
+
+    #[test]
+    fn percent_encodes_file_uris() {
+        assert_eq!(
+            file_uri("locale/my file ż#1.txt"),
+            "locale/my%20file%20%C5%BC%231.txt"
+        );
+        assert_eq!(file_uri("a:b.txt"), "a%3Ab.txt");
+        assert_eq!(file_uri("/tmp/a b.txt"), "file:///tmp/a%20b.txt");
+    }
+
+    #[test]
+    fn picks_most_severe_exit_code() {
+        let warning = Diagnostic::warning("missing-translation", "no EN");
+        let error = Diagnostic::error("argument-mismatch", "arguments differ");
+        let syntax = Diagnostic::error("syntax", "bad line").with_kind(DiagnosticKind::Parse);
+        let io = Diagnostic::error("io", "not found").with_kind(DiagnosticKind::Io);
+
+        assert_eq!(exit_code(&[], Severity::Warning), ExitCode::Success);
+        assert_eq!(
+            exit_code(std::slice::from_ref(&warning), Severity::Error),
+            ExitCode::Success
+        );
+        assert_eq!(
+            exit_code(std::slice::from_ref(&warning), Severity::Warning),
+            ExitCode::Findings
+        );
+        assert_eq!(
+            exit_code(&[warning, error], Severity::Error),
+            ExitCode::Findings
+        );
+        assert_eq!(
+            exit_code(&[io, syntax.clone()], Severity::Error),
+            ExitCode::IoError
+        );
+        assert_eq!(exit_code(&[syntax], Severity::Error), ExitCode::ParseError);
+    }
+
+    #[test]
+    fn classifies_errors_by_type() {
+        let code = |d: Diagnostic| exit_code(&[d], Severity::Error);
+        let location = Location::default();
+
+        let mismatch = borrowed_section("a", vec![(Language::PL, "%d"), (Language::EN, "%s")]);
+        assert_eq!(
+            exit_code(&mismatch.argument_mismatches(), Severity::Error),
+            ExitCode::Findings
+        );
+        assert_eq!(
+            code(PoError::Syntax("msgid".to_string(), location).into()),
+            ExitCode::ParseError
+        );
+        assert_eq!(
+            code(PoError::Io(std::io::ErrorKind::NotFound.into()).into()),
+            ExitCode::IoError
+        );
+        let conflict = Conflict {
+            label: "a",
+            language: Language::PL,
+            base: Some("pl"),
+            ours: Some("ours"),
+            theirs: None,
+        };
+        assert_eq!(conflict.diagnostic().code, "delete-conflict");
+        assert_eq!(code(conflict.diagnostic()), ExitCode::Findings);
+    }
+}
@@ -0,0 +1,293 @@
+use crate::section::Language;
+use chardetng::EncodingDetector;
+use encoding_rs::{
+    Encoding, BIG5, EUC_KR, GBK, REPLACEMENT, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250,
+    WINDOWS_1251, WINDOWS_1252, WINDOWS_1253, WINDOWS_1254, WINDOWS_1256, WINDOWS_1258,
+};
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind};
+use std::iter::FromIterator;
+use std::path::Path;
+
+/// Codepage the game client uses for the language. Languages without a known codepage are
+/// not restricted.
+pub fn language_encoding(lang: Language) -> &'static Encoding {
+    match lang.code() {
+        "PL" | "CZ" | "HU" | "RO" => WINDOWS_1250,
+        "RU" | "BG" | "UA" => WINDOWS_1251,
+        "EN" | "DE" | "IT" | "ES" | "FR" | "PT" | "NL" | "DK" => WINDOWS_1252,
+        "GR" => WINDOWS_1253,
+        "TR" => WINDOWS_1254,
+        "AE" => WINDOWS_1256,
+        "VN" => WINDOWS_1258,
+        "KR" => EUC_KR,
+        "JP" => SHIFT_JIS,
+        "CN" => GBK,
+        "TW" => BIG5,
+        _ => UTF_8,
+    }
+}
+
+/// Codepages configured for languages, the remaining ones use [`language_encoding`].
+#[derive(Clone, Debug, Default)]
+pub struct Codepages(BTreeMap<Language, &'static Encoding>);
+
+impl Codepages {
+    pub fn get(&self, lang: Language) -> &'static Encoding {
+        self.0
+            .get(&lang)
+            .copied()
+            .unwrap_or_else(|| language_encoding(lang))
+    }
+}
+
+impl FromIterator<(Language, &'static Encoding)> for Codepages {
+    fn from_iter<I: IntoIterator<Item = (Language, &'static Encoding)>>(iter: I) -> Self {
+        Codepages(iter.into_iter().collect())
+    }
+}
+
+/// Parses a codepage assignment such as `TR=cp1254`.
+pub fn parse_codepage(assignment: &str) -> Result<(Language, &'static Encoding), String> {
+    let (lang, label) = assignment
+        .split_once('=')
+        .ok_or_else(|| format!("expected LANGUAGE=ENCODING, got {}", assignment))?;
+    Ok((lang.parse()?, parse_encoding(label)?))
+}
+
+/// Characters of the text which the encoding cannot represent, each listed once.
+pub fn unrepresentable_chars(text: &str, encoding: &'static Encoding) -> Vec<char> {
+    let mut chars: Vec<char> = vec![];
+    let mut buffer = [0; 4];
+    for c in text.chars() {
+        let (_, _, unmappable) = encoding.encode(c.encode_utf8(&mut buffer));
+        if unmappable && !chars.contains(&c) {
+            chars.push(c);
+        }
+    }
+
+    chars
+}
+
+/// Parses an encoding label such as `cp1250`, `windows-1254` or `euc-kr`. Encodings files
+/// cannot be written in are rejected.
+pub fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    match Encoding::for_label(label.trim().as_bytes()) {
+        Some(e) if e == REPLACEMENT => Err(format!("encoding {} cannot be written", label)),
+        Some(e) => Ok(e),
+        None => Err(format!("unknown encoding {}", label)),
+    }
+}
+
+/// Guesses the encoding of file data. A BOM or valid UTF-8 wins, legacy codepages are guessed
+/// from the byte statistics.
+pub fn detect_encoding(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Decodes data in the given encoding, failing on malformed bytes instead of replacing them.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> std::io::Result<String> {
+    let (encoding, bytes) = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_length)) => (bom_encoding, &bytes[bom_length..]),
+        None => (encoding, bytes),
+    };
+
+    encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("data is not valid {}", encoding.name()),
+            )
+        })
+}
+
+/// Encodes text in the given encoding, failing on characters the codepage cannot represent.
+pub fn encode(text: &str, encoding: &'static Encoding) -> std::io::Result<Vec<u8>> {
+    // encoding_rs only decodes UTF-16
+    if encoding == UTF_16LE {
+        return Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect());
+    }
+    if encoding == UTF_16BE {
+        return Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect());
+    }
+
+    let (bytes, used, unmappable) = encoding.encode(text);
+    if unmappable {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("text cannot be represented in {}", used.name()),
+        ));
+    }
+
+    Ok(bytes.into_owned())
+}
+
+/// Encoding of a file and whether it starts with a byte order mark.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl From<&'static Encoding> for FileEncoding {
+    fn from(encoding: &'static Encoding) -> Self {
+        FileEncoding {
+            encoding,
+            bom: false,
+        }
+    }
+}
+
+/// Decodes file data in the configured encoding, or in the detected one when none is given.
+/// A BOM overrides both.
+pub fn decode_file_data(
+    bytes: &[u8],
+    encoding: Option<&'static Encoding>,
+) -> std::io::Result<(String, FileEncoding)> {
+    let encoding = match Encoding::for_bom(bytes) {
+        Some((encoding, _)) => FileEncoding {
+            encoding,
+            bom: true,
+        },
+        None => FileEncoding::from(encoding.unwrap_or_else(|| detect_encoding(bytes))),
+    };
+
+    Ok((decode(bytes, encoding.encoding)?, encoding))
+}
+
+/// Encodes file data, starting with a BOM when the encoding has one.
+pub fn encode_file_data(text: &str, encoding: FileEncoding) -> std::io::Result<Vec<u8>> {
+    let mut bytes = match encoding.encoding {
+        _ if !encoding.bom => vec![],
+        e if e == UTF_16LE => vec![0xff, 0xfe],
+        e if e == UTF_16BE => vec![0xfe, 0xff],
+        _ => vec![0xef, 0xbb, 0xbf],
+    };
+    bytes.extend(encode(text, encoding.encoding)?);
+
+    Ok(bytes)
+}
+
+/// Reads a file in the configured encoding, or in the detected one when none is given.
+/// Returns the text together with the encoding it was read in.
+pub fn read_encoded<T: AsRef<Path>>(
+    filepath: T,
+    encoding: Option<&'static Encoding>,
+) -> std::io::Result<(String, FileEncoding)> {
+    decode_file_data(&std::fs::read(filepath)?, encoding)
+}
+
+pub fn write_encoded<T, E>(filepath: T, text: &str, encoding: E) -> std::io::Result<()>
+where
+    T: AsRef<Path>,
+    E: Into<FileEncoding>,
+{
+    std::fs::write(filepath, encode_file_data(text, encoding.into())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::encoding::{
+        decode, decode_file_data, detect_encoding, encode, encode_file_data, parse_codepage,
+        parse_encoding, unrepresentable_chars, Codepages, FileEncoding,
+    };
+    use crate::section::Language;
+    use encoding_rs::{
+        EUC_KR, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1250, WINDOWS_1252, WINDOWS_1254,
+    };
+
+    #[test]
+    fn parses_codepage_labels() {
+        assert_eq!(parse_encoding("cp1250"), Ok(WINDOWS_1250));
+        assert_eq!(parse_encoding("windows-1254"), Ok(WINDOWS_1254));
+        assert_eq!(parse_encoding("EUC-KR"), Ok(EUC_KR));
+        assert!(parse_encoding("cp9999").is_err());
+        assert_eq!(parse_encoding("utf-16le"), Ok(UTF_16LE));
+        assert_eq!(parse_encoding("UTF-16BE"), Ok(UTF_16BE));
+        assert_eq!(
+            parse_encoding("iso-2022-kr"),
+            Err("encoding iso-2022-kr cannot be written".to_string())
+        );
+    }
+
+    #[test]
+    fn detects_utf8_and_legacy_codepages() {
+        assert_eq!(detect_encoding("zażółć gęślą jaźń".as_bytes()), UTF_8);
+
+        let polish = encode("Zażółć gęślą jaźń, świeży chleb", WINDOWS_1250).unwrap();
+        assert_eq!(detect_encoding(&polish), WINDOWS_1250);
+    }
+
+    #[test]
+    fn round_trips_legacy_codepages() {
+        let turkish = "Ağaç dalı çiçek şeker";
+        let bytes = encode(turkish, WINDOWS_1254).unwrap();
+
+        assert_eq!(bytes.len(), turkish.chars().count());
+        assert_eq!(decode(&bytes, WINDOWS_1254).unwrap(), turkish);
+    }
+
+    #[test]
+    fn preserves_byte_order_marks() {
+        let utf8 = b"\xef\xbb\xbfPL\t\"\xc5\xbc\"\n";
+        let (text, encoding) = decode_file_data(utf8, Some(WINDOWS_1250)).unwrap();
+        assert_eq!(text, "PL\t\"ż\"\n");
+        assert_eq!(
+            encoding,
+            FileEncoding {
+                encoding: UTF_8,
+                bom: true
+            }
+        );
+        assert_eq!(encode_file_data(&text, encoding).unwrap(), utf8);
+
+        let utf16: Vec<u8> = vec![0xff, 0xfe, b'P', 0, b'L', 0, 0x7c, 0x01];
+        let (text, encoding) = decode_file_data(&utf16, None).unwrap();
+        assert_eq!(text, "PLż");
+        assert_eq!(encoding.encoding, UTF_16LE);
+        assert_eq!(encode_file_data(&text, encoding).unwrap(), utf16);
+
+        let (_, encoding) = decode_file_data(b"PL", None).unwrap();
+        assert!(!encoding.bom);
+        assert_eq!(encode_file_data("PL", encoding).unwrap(), b"PL");
+    }
+
+    #[test]
+    fn rejects_unrepresentable_text() {
+        assert!(encode("ğ", WINDOWS_1250).is_err());
+        assert!(decode(&[0xff, 0xff], UTF_8).is_err());
+    }
+
+    #[test]
+    fn configures_codepages_per_language() {
+        let codepages: Codepages = vec![parse_codepage("de=cp1250").unwrap()]
+            .into_iter()
+            .collect();
+
+        assert_eq!(codepages.get(Language::DE), WINDOWS_1250);
+        assert_eq!(codepages.get(Language::TR), WINDOWS_1254);
+        assert_eq!(codepages.get(Language::from("XX")), UTF_8);
+        assert!(parse_codepage("DE").is_err());
+        assert!(parse_codepage("DE=nope").is_err());
+    }
+
+    #[test]
+    fn finds_unrepresentable_chars() {
+        assert_eq!(
+            unrepresentable_chars("Dağ ğüş ü", WINDOWS_1252),
+            vec!['ğ', 'ş']
+        );
+        assert!(unrepresentable_chars("Dağ", WINDOWS_1254).is_empty());
+    }
+}
@@ -1,11 +1,31 @@
-use crate::parse::{parse_clientside, ParseError};
+use crate::diagnostic::Diagnostic;
 use crate::section::{Language, Section};
+use std::collections::BTreeMap;
 
-pub fn find_incomplete_sections(sections: Vec<Section>) -> Vec<&str> {
+/// Warnings for every expected language the section has no translation in.
+pub fn find_missing_translations(section: &Section, languages: &[Language]) -> Vec<Diagnostic> {
+    languages
+        .iter()
+        .filter(|l| !section.translations.contains_key(l))
+        .map(|l| {
+            Diagnostic::warning(
+                "missing-translation",
+                format!("label {} has no translation in {}", section.label, l),
+            )
+            .with_label(section.label)
+            .with_language(*l)
+        })
+        .collect()
+}
+
+pub fn find_incomplete_sections<'a>(
+    sections: Vec<Section<'a>>,
+    languages: &[Language],
+) -> Vec<&'a str> {
     let mut unfinished_translations = vec![];
 
     for s in sections.iter() {
-        if s.translations.len() != Language::variants_count() {
+        if languages.iter().any(|l| !s.translations.contains_key(l)) {
             unfinished_translations.push(s.label);
         }
     }
@@ -13,47 +33,80 @@ pub fn find_incomplete_sections(sections: Vec<Section>) -> Vec<&str> {
     unfinished_translations
 }
 
-pub fn find_missing_labels<'a, T>(data: T, second_data: T) -> Result<Vec<String>, ParseError>
-where
-    T: AsRef<str>,
-{
-    let first_map = parse_clientside(data)?;
-    let second_map = parse_clientside(second_data)?;
-    let mut missing_vec: Vec<String> = vec![];
-
-    for (x, _) in first_map.iter() {
-        if !second_map.contains_key(x) {
-            missing_vec.push(x.to_owned());
-        }
-    }
-
-    Ok(missing_vec)
+/// Warnings for labels of the first file which are missing in the second one.
+pub fn find_missing_labels(
+    first_map: &BTreeMap<&str, &str>,
+    second_map: &BTreeMap<&str, &str>,
+) -> Vec<Diagnostic> {
+    first_map
+        .keys()
+        .filter(|x| !second_map.contains_key(*x))
+        .map(|x| {
+            Diagnostic::warning(
+                "missing-label",
+                format!("label {} is missing in the compared file", x),
+            )
+            .with_label(*x)
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::find::find_incomplete_sections;
-    use crate::section::Language::{EN, PL};
+    use crate::find::{find_incomplete_sections, find_missing_labels, find_missing_translations};
+    use crate::section::Language;
     use crate::section::Section;
 
     #[test]
     fn finds_incomplete_translations() {
         let mut s1 = Section::new();
         s1.label = "test";
-        s1.translations.insert(PL, "asd");
+        s1.translations.insert(Language::PL, "asd");
 
-        assert_eq!(find_incomplete_sections(vec![s1]), vec!["test"])
+        assert_eq!(
+            find_incomplete_sections(vec![s1], &[Language::PL, Language::EN]),
+            vec!["test"]
+        )
     }
 
     #[test]
     fn not_return_complete_sections() {
         let mut s1 = Section::new();
         s1.label = "test";
-        s1.translations.insert(PL, "asd");
-        s1.translations.insert(EN, "asd");
+        s1.translations.insert(Language::PL, "asd");
+        s1.translations.insert(Language::EN, "asd");
 
         let empty: Vec<&str> = vec![];
 
-        assert_eq!(find_incomplete_sections(vec![s1]), empty)
+        assert_eq!(
+            find_incomplete_sections(vec![s1], &[Language::PL, Language::EN]),
+            empty
+        )
+    }
+
+    #[test]
+    fn reports_each_missing_language() {
+        let mut s1 = Section::new();
+        s1.label = "test";
+        s1.translations.insert(Language::EN, "asd");
+
+        let diagnostics =
+            find_missing_translations(&s1, &[Language::PL, Language::EN, Language::DE]);
+        let languages: Vec<_> = diagnostics.iter().map(|d| d.language).collect();
+
+        assert_eq!(languages, vec![Some(Language::PL), Some(Language::DE)]);
+        assert_eq!(diagnostics[0].label.as_deref(), Some("test"));
+    }
+
+    #[test]
+    fn reports_missing_labels() {
+        let first = vec![("a", "1"), ("b", "2")];
+        let second = vec![("a", "1")];
+
+        let diagnostics =
+            find_missing_labels(&first.into_iter().collect(), &second.into_iter().collect());
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].label.as_deref(), Some("b"));
     }
 }
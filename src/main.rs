@@ -1,11 +1,26 @@
 use crate::cmd::run;
 
+mod cli;
 mod cmd;
 mod convert;
+mod diagnostic;
+mod encoding;
 mod find;
+mod markup;
+mod merge;
+mod model;
 mod parse;
+mod placeholder;
+mod po;
 mod section;
+mod sheet;
+mod stats;
+mod syntax;
+#[cfg(test)]
+mod test_support;
+mod tmx;
+mod xliff;
 
-fn main() -> std::io::Result<()> {
-    run()
+fn main() {
+    std::process::exit(run() as i32);
 }
@@ -0,0 +1,155 @@
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum MarkupError {
+    #[error("color code {0} is not |c followed by 8 hex digits")]
+    InvalidColor(String),
+    #[error("|r without an open color")]
+    UnexpectedColorEnd,
+    #[error("{0} color(s) not closed with |r")]
+    UnclosedColor(usize),
+    #[error("hyperlink {0} is not closed with |h")]
+    UnclosedLink(String),
+    #[error("|h without an open hyperlink")]
+    UnexpectedLinkEnd,
+    #[error("colors {0:?} differ from {1:?} in the reference language")]
+    ColorMismatch(Vec<String>, Vec<String>),
+    #[error("hyperlinks {0:?} differ from {1:?} in the reference language")]
+    LinkMismatch(Vec<String>, Vec<String>),
+}
+
+/// Color and hyperlink tokens of a text. Both lists are sorted, since word order and with it
+/// the order of the tokens may differ between languages.
+#[derive(Debug, Default, PartialEq)]
+pub struct Markup {
+    /// Colors as `AARRGGBB` in uppercase.
+    pub colors: Vec<String>,
+    /// Hyperlink data between `|H` and `|h`, e.g. `item:19:0:0`.
+    pub links: Vec<String>,
+}
+
+impl Markup {
+    /// Checks that the text uses the same tokens as the reference.
+    pub fn compare(&self, reference: &Markup) -> Result<(), MarkupError> {
+        if self.colors != reference.colors {
+            return Err(MarkupError::ColorMismatch(
+                self.colors.clone(),
+                reference.colors.clone(),
+            ));
+        }
+        if self.links != reference.links {
+            return Err(MarkupError::LinkMismatch(
+                self.links.clone(),
+                reference.links.clone(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses `|cAARRGGBB ... |r` color runs and `|Hdata|htext|h` hyperlinks, verifying that they
+/// are well-formed and balanced. `||` is an escaped pipe.
+pub fn parse_markup(text: &str) -> Result<Markup, MarkupError> {
+    let mut markup = Markup::default();
+    let mut open_colors = 0;
+    let mut rest = text;
+
+    while let Some(position) = rest.find('|') {
+        rest = &rest[position + 1..];
+        match rest.chars().next() {
+            Some('|') => rest = &rest[1..],
+            Some('c') => {
+                let color = rest[1..].get(..8).unwrap_or(&rest[1..]);
+                if color.len() != 8 || !color.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(MarkupError::InvalidColor(format!("|c{}", color)));
+                }
+                markup.colors.push(color.to_ascii_uppercase());
+                open_colors += 1;
+                rest = &rest[9..];
+            }
+            Some('r') => {
+                if open_colors == 0 {
+                    return Err(MarkupError::UnexpectedColorEnd);
+                }
+                open_colors -= 1;
+                rest = &rest[1..];
+            }
+            Some('H') => {
+                let data_end = rest
+                    .find("|h")
+                    .ok_or_else(|| MarkupError::UnclosedLink(format!("|{}", rest)))?;
+                let link_text = &rest[data_end + 2..];
+                let text_end = link_text.find("|h").ok_or_else(|| {
+                    MarkupError::UnclosedLink(format!("|{}", &rest[..data_end + 2]))
+                })?;
+
+                markup.links.push(rest[1..data_end].to_string());
+                rest = &link_text[text_end + 2..];
+            }
+            Some('h') => return Err(MarkupError::UnexpectedLinkEnd),
+            _ => {}
+        }
+    }
+
+    if open_colors > 0 {
+        return Err(MarkupError::UnclosedColor(open_colors));
+    }
+
+    markup.colors.sort();
+    markup.links.sort();
+    Ok(markup)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markup::{parse_markup, MarkupError};
+
+    #[test]
+    fn parses_colors_and_links() {
+        let markup =
+            parse_markup("|cffff0000Warning|r: |cFF00FF00|Hitem:19:0|h[Sword]|h|r || ok").unwrap();
+
+        assert_eq!(markup.colors, vec!["FF00FF00", "FFFF0000"]);
+        assert_eq!(markup.links, vec!["item:19:0"]);
+        assert_eq!(parse_markup("plain text").unwrap().colors.len(), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_markup() {
+        assert_eq!(
+            parse_markup("|cffzz0000red|r"),
+            Err(MarkupError::InvalidColor("|cffzz0000".to_string()))
+        );
+        assert_eq!(
+            parse_markup("|cff"),
+            Err(MarkupError::InvalidColor("|cff".to_string()))
+        );
+        assert_eq!(parse_markup("text|r"), Err(MarkupError::UnexpectedColorEnd));
+        assert_eq!(
+            parse_markup("|cffff0000red"),
+            Err(MarkupError::UnclosedColor(1))
+        );
+        assert_eq!(
+            parse_markup("|Hitem:1|h[Sword]"),
+            Err(MarkupError::UnclosedLink("|Hitem:1|h".to_string()))
+        );
+        assert_eq!(
+            parse_markup("[Sword]|h"),
+            Err(MarkupError::UnexpectedLinkEnd)
+        );
+    }
+
+    #[test]
+    fn compares_against_reference() {
+        let reference = parse_markup("|cffff0000a|r |cff00ff00b|r").unwrap();
+        let swapped = parse_markup("|cff00ff00b|r |cffff0000a|r").unwrap();
+        let missing = parse_markup("|cff00ff00b|r").unwrap();
+
+        assert!(swapped.compare(&reference).is_ok());
+        assert!(matches!(
+            missing.compare(&reference),
+            Err(MarkupError::ColorMismatch(..))
+        ));
+    }
+}
@@ -0,0 +1,805 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::parse::{merge_sections, ParseError};
+use crate::section::{Language, Section};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// How translations from the newer file are applied to the base file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MergeStrategy {
+    /// Only labels missing in the base are added.
+    AddOnly,
+    /// Missing labels and missing languages of existing labels are added.
+    FillGaps,
+    /// Newer translations always win.
+    Overwrite,
+    /// Newer translations win unless they are a copy of another language of the newer label.
+    /// The source text, its first language, is never treated as a copy.
+    PreferBase,
+}
+
+impl MergeStrategy {
+    pub fn variants() -> Vec<MergeStrategy> {
+        vec![
+            MergeStrategy::Overwrite,
+            MergeStrategy::AddOnly,
+            MergeStrategy::FillGaps,
+            MergeStrategy::PreferBase,
+        ]
+    }
+}
+
+impl Display for MergeStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeStrategy::AddOnly => write!(f, "Only add missing labels"),
+            MergeStrategy::FillGaps => write!(f, "Add missing labels and languages"),
+            MergeStrategy::Overwrite => write!(f, "Newer translations win"),
+            MergeStrategy::PreferBase => {
+                write!(
+                    f,
+                    "Newer translations win unless copied from another language"
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "add-only" => Ok(MergeStrategy::AddOnly),
+            "fill-gaps" => Ok(MergeStrategy::FillGaps),
+            "overwrite" => Ok(MergeStrategy::Overwrite),
+            "prefer-base" => Ok(MergeStrategy::PreferBase),
+            m => Err(format!(
+                "invalid strategy {}, expected add-only, fill-gaps, overwrite or prefer-base",
+                m
+            )),
+        }
+    }
+}
+
+/// Everything a merge changed in the base file.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct MergeReport<'a> {
+    pub added_labels: Vec<&'a str>,
+    pub overwritten: Vec<Overwritten<'a>>,
+    pub added_languages: Vec<AddedLanguage<'a>>,
+    pub untouched_labels: Vec<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Overwritten<'a> {
+    pub label: &'a str,
+    pub language: Language,
+    pub old: &'a str,
+    pub new: &'a str,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct AddedLanguage<'a> {
+    pub label: &'a str,
+    pub language: Language,
+    pub text: &'a str,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ReportFormat::Text),
+            "json" => Ok(ReportFormat::Json),
+            f => Err(format!(
+                "invalid report format {}, expected text or json",
+                f
+            )),
+        }
+    }
+}
+
+impl<'a> MergeReport<'a> {
+    /// Compares the base sections with the result of merging them.
+    pub fn new(base: &[Section<'a>], merged: &[Section<'a>]) -> Self {
+        let mut report = MergeReport::default();
+
+        for s in merged {
+            let old = match base.iter().find(|b| b.label == s.label) {
+                Some(old) => old,
+                None => {
+                    report.added_labels.push(s.label);
+                    continue;
+                }
+            };
+
+            let mut touched = false;
+            for (lang, text) in &s.translations {
+                match old.translations.get(lang) {
+                    Some(old_text) if old_text == text => {}
+                    Some(old_text) => {
+                        touched = true;
+                        report.overwritten.push(Overwritten {
+                            label: s.label,
+                            language: *lang,
+                            old: old_text,
+                            new: text,
+                        });
+                    }
+                    None => {
+                        touched = true;
+                        report.added_languages.push(AddedLanguage {
+                            label: s.label,
+                            language: *lang,
+                            text,
+                        });
+                    }
+                }
+            }
+
+            if !touched {
+                report.untouched_labels.push(s.label);
+            }
+        }
+
+        report
+    }
+
+    pub fn render(&self, format: ReportFormat) -> serde_json::Result<String> {
+        match format {
+            ReportFormat::Text => Ok(self.to_string()),
+            ReportFormat::Json => serde_json::to_string_pretty(self),
+        }
+    }
+}
+
+impl Display for MergeReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Added labels: {}", self.added_labels.len())?;
+        writeln!(f, "Overwritten translations: {}", self.overwritten.len())?;
+        writeln!(f, "Added languages: {}", self.added_languages.len())?;
+        writeln!(f, "Untouched labels: {}", self.untouched_labels.len())?;
+
+        for label in &self.added_labels {
+            writeln!(f, "+ {}", label)?;
+        }
+        for o in &self.overwritten {
+            writeln!(
+                f,
+                "~ {} [{}]: \"{}\" -> \"{}\"",
+                o.label, o.language, o.old, o.new
+            )?;
+        }
+        for a in &self.added_languages {
+            writeln!(f, "+ {} [{}]: \"{}\"", a.label, a.language, a.text)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum MergeError {
+    #[error("strategy prefer-base needs a source language, client-side files hold a single one")]
+    NoSourceLanguage,
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl From<MergeError> for Diagnostic {
+    fn from(error: MergeError) -> Self {
+        let message = error.to_string();
+        match error {
+            MergeError::NoSourceLanguage => {
+                Diagnostic::error("unsupported-strategy", message).with_kind(DiagnosticKind::Usage)
+            }
+            MergeError::Parse(e) => e.into(),
+            MergeError::Io(e) => ParseError::Io(e).into(),
+        }
+    }
+}
+
+/// Merges two client-side files, keeping the label order of the base and appending new labels.
+/// Client files hold a single language, so `FillGaps` only fills labels with an empty
+/// translation and `PreferBase`, which needs a source language, is an error.
+pub fn merge_clientside<'a>(
+    mut base: Vec<(&'a str, &'a str)>,
+    new: Vec<(&'a str, &'a str)>,
+    strategy: MergeStrategy,
+) -> Result<Vec<(&'a str, &'a str)>, MergeError> {
+    if strategy == MergeStrategy::PreferBase {
+        return Err(MergeError::NoSourceLanguage);
+    }
+
+    for (label, text) in new {
+        let entry = match base.iter_mut().find(|(l, _)| *l == label) {
+            Some(entry) => entry,
+            None => {
+                base.push((label, text));
+                continue;
+            }
+        };
+
+        match strategy {
+            MergeStrategy::AddOnly => {}
+            MergeStrategy::FillGaps => {
+                if entry.1.is_empty() {
+                    entry.1 = text;
+                }
+            }
+            MergeStrategy::Overwrite | MergeStrategy::PreferBase => entry.1 = text,
+        }
+    }
+
+    Ok(base)
+}
+
+/// Index of the input each translation of the merged sections was taken from.
+pub type Sources<'a> = BTreeMap<(&'a str, Language), usize>;
+
+/// Merges any number of files pairwise with `merge_sections`. Later inputs take precedence.
+pub fn merge_many<'a>(
+    inputs: Vec<Vec<Section<'a>>>,
+    strategy: MergeStrategy,
+) -> (Vec<Section<'a>>, Sources<'a>) {
+    let mut merged: Vec<Section<'a>> = vec![];
+    let mut sources = Sources::new();
+
+    for (i, sections) in inputs.into_iter().enumerate() {
+        let before: BTreeMap<(&str, Language), &str> = merged
+            .iter()
+            .flat_map(|s| s.translations.iter().map(move |(l, t)| ((s.label, *l), *t)))
+            .collect();
+
+        merged = merge_sections(merged, sections, strategy);
+
+        for s in &merged {
+            for (l, t) in &s.translations {
+                if before.get(&(s.label, *l)) != Some(t) {
+                    sources.insert((s.label, *l), i);
+                }
+            }
+        }
+    }
+
+    (merged, sources)
+}
+
+/// Start of the comment naming the inputs of a merged section.
+pub const SOURCES_COMMENT: &str = "# sources:";
+
+/// Comment naming the input of each translation of the section, `None` when none of them has
+/// a recorded source.
+pub fn sources_comment<T>(section: &Section, sources: &Sources, names: &[T]) -> Option<String>
+where
+    T: AsRef<str>,
+{
+    let origins: Vec<String> = section
+        .translations
+        .keys()
+        .filter_map(|l| {
+            let name = names.get(*sources.get(&(section.label, *l))?)?;
+            Some(format!("{}={}", l, name.as_ref()))
+        })
+        .collect();
+
+    match origins.is_empty() {
+        true => None,
+        false => Some(format!("{} {}", SOURCES_COMMENT, origins.join(", "))),
+    }
+}
+
+/// Translation changed in different ways on both sides since the common ancestor.
+#[derive(Debug, PartialEq)]
+pub struct Conflict<'a> {
+    pub label: &'a str,
+    pub language: Language,
+    pub base: Option<&'a str>,
+    pub ours: Option<&'a str>,
+    pub theirs: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictKind {
+    /// Both sides changed the translation.
+    Modified,
+    /// We deleted the translation or its label, they changed it.
+    DeletedByOurs,
+    /// They deleted the translation or its label, we changed it.
+    DeletedByTheirs,
+}
+
+impl Conflict<'_> {
+    pub fn kind(&self) -> ConflictKind {
+        match (self.ours, self.theirs) {
+            (None, _) => ConflictKind::DeletedByOurs,
+            (_, None) => ConflictKind::DeletedByTheirs,
+            _ => ConflictKind::Modified,
+        }
+    }
+
+    /// Error reporting the conflict, merges with conflicts still write their output.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let deleted_in = match self.kind() {
+            ConflictKind::Modified => None,
+            ConflictKind::DeletedByOurs => Some("ours"),
+            ConflictKind::DeletedByTheirs => Some("theirs"),
+        };
+        let (code, message) = match deleted_in {
+            None => (
+                "merge-conflict",
+                format!(
+                    "translation {} in label {} was changed on both sides",
+                    self.language, self.label
+                ),
+            ),
+            Some(side) => (
+                "delete-conflict",
+                format!(
+                    "translation {} in label {} was deleted in {} and changed in the other file",
+                    self.language, self.label, side
+                ),
+            ),
+        };
+
+        Diagnostic::error(code, message)
+            .with_label(self.label)
+            .with_language(self.language)
+    }
+}
+
+/// Result of a three-way merge. Conflicting translations keep our version in `sections`, labels
+/// we deleted but they changed are kept without the deleted translations.
+#[derive(Debug, PartialEq)]
+pub struct ThreeWayMerge<'a> {
+    pub sections: Vec<Section<'a>>,
+    pub conflicts: Vec<Conflict<'a>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictResolution {
+    Markers,
+    Ours,
+    Theirs,
+}
+
+impl FromStr for ConflictResolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markers" => Ok(ConflictResolution::Markers),
+            "ours" => Ok(ConflictResolution::Ours),
+            "theirs" => Ok(ConflictResolution::Theirs),
+            r => Err(format!(
+                "invalid resolution {}, expected markers, ours or theirs",
+                r
+            )),
+        }
+    }
+}
+
+fn find<'s, 'a>(sections: &'s [Section<'a>], label: &str) -> Option<&'s Section<'a>> {
+    sections.iter().find(|s| s.label == label)
+}
+
+/// Merges changes made in `ours` and `theirs` since they were branched from `base`.
+/// Translations changed on one side only are taken from that side, the rest are conflicts.
+pub fn merge_three_way<'a>(
+    base: &[Section<'a>],
+    ours: &[Section<'a>],
+    theirs: &[Section<'a>],
+) -> ThreeWayMerge<'a> {
+    let mut labels: Vec<&'a str> = ours.iter().map(|s| s.label).collect();
+    for s in theirs {
+        if !labels.contains(&s.label) {
+            labels.push(s.label);
+        }
+    }
+
+    let mut sections = vec![];
+    let mut conflicts = vec![];
+
+    for label in labels {
+        let b = find(base, label);
+        let o = find(ours, label);
+        let t = find(theirs, label);
+
+        let languages: BTreeSet<Language> = [b, o, t]
+            .iter()
+            .flatten()
+            .flat_map(|s| s.translations.keys().copied())
+            .collect();
+
+        let mut section = Section::new();
+        section.label = label;
+
+        for lang in languages {
+            let get = |s: Option<&Section<'a>>| s.and_then(|s| s.translations.get(&lang).copied());
+            let (base_t, ours_t, theirs_t) = (get(b), get(o), get(t));
+
+            let merged = if ours_t == theirs_t || theirs_t == base_t {
+                ours_t
+            } else if ours_t == base_t {
+                theirs_t
+            } else {
+                conflicts.push(Conflict {
+                    label,
+                    language: lang,
+                    base: base_t,
+                    ours: ours_t,
+                    theirs: theirs_t,
+                });
+                ours_t
+            };
+
+            if let Some(text) = merged {
+                section.translations.insert(lang, text);
+            }
+        }
+
+        // labels removed on one side and left untouched on the other stay removed
+        let deleted_by_theirs = b.is_some() && t.is_none();
+        if (o.is_some() && !deleted_by_theirs)
+            || !section.translations.is_empty()
+            || conflicts.iter().any(|c: &Conflict| c.label == label)
+        {
+            sections.push(section);
+        }
+    }
+
+    ThreeWayMerge {
+        sections,
+        conflicts,
+    }
+}
+
+/// Replaces the translation of a conflicting label with the chosen version.
+pub fn resolve_conflict<'a>(
+    sections: &mut Vec<Section<'a>>,
+    conflict: &Conflict<'a>,
+    text: Option<&'a str>,
+) {
+    let pos = match sections.iter().position(|s| s.label == conflict.label) {
+        Some(pos) => pos,
+        None => {
+            let mut section = Section::new();
+            section.label = conflict.label;
+            sections.push(section);
+            sections.len() - 1
+        }
+    };
+
+    match text {
+        Some(text) => {
+            sections[pos].translations.insert(conflict.language, text);
+        }
+        None => {
+            sections[pos].translations.remove(&conflict.language);
+        }
+    }
+}
+
+fn translation_line(lang: Language, text: Option<&str>) -> String {
+    match text {
+        Some(text) => format!("\t{lang}\t\"{text}\"\n", lang = lang, text = text),
+        None => "".to_string(),
+    }
+}
+
+/// Generates the file content, writing git-style conflict markers around conflicting translations.
+pub fn generate_with_conflicts(sections: &[Section], conflicts: &[Conflict]) -> String {
+    let mut out = String::new();
+
+    for s in sections {
+        let section_conflicts: Vec<&Conflict> =
+            conflicts.iter().filter(|c| c.label == s.label).collect();
+        if section_conflicts.is_empty() {
+            out.push_str(&format!("{}\n\n", s.generate()));
+            continue;
+        }
+
+        out.push_str(&format!("section\n\tTXT\t\"{}\"\n", s.label));
+        for (lang, text) in &s.translations {
+            if section_conflicts.iter().all(|c| c.language != *lang) {
+                out.push_str(&translation_line(*lang, Some(text)));
+            }
+        }
+        for c in section_conflicts {
+            let (ours, theirs) = match c.kind() {
+                ConflictKind::Modified => ("ours", "theirs"),
+                ConflictKind::DeletedByOurs => ("ours (deleted)", "theirs"),
+                ConflictKind::DeletedByTheirs => ("ours", "theirs (deleted)"),
+            };
+            out.push_str(&format!("<<<<<<< {}\n", ours));
+            out.push_str(&translation_line(c.language, c.ours));
+            out.push_str("||||||| base\n");
+            out.push_str(&translation_line(c.language, c.base));
+            out.push_str("=======\n");
+            out.push_str(&translation_line(c.language, c.theirs));
+            out.push_str(&format!(">>>>>>> {}\n", theirs));
+        }
+        out.push_str("end\n\n");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::{
+        generate_with_conflicts, merge_clientside, merge_many, merge_three_way, resolve_conflict,
+        sources_comment, AddedLanguage, Conflict, ConflictKind, MergeError, MergeReport,
+        MergeStrategy, Overwritten, ReportFormat,
+    };
+    use crate::parse::merge_sections;
+    use crate::section::Language;
+    use crate::test_support::section;
+
+    #[test]
+    fn reports_merge_changes() {
+        let base = vec![
+            section("a", &[(Language::PL, "pl"), (Language::EN, "en")]),
+            section("b", &[(Language::PL, "pl")]),
+            section("d", &[(Language::PL, "pl")]),
+        ];
+        let new = vec![
+            section("a", &[(Language::PL, "pl2")]),
+            section("b", &[(Language::PL, "pl"), (Language::EN, "en")]),
+            section("c", &[(Language::PL, "new")]),
+        ];
+
+        let merged = merge_sections(base.clone(), new, MergeStrategy::Overwrite);
+        let report = MergeReport::new(&base, &merged);
+
+        assert_eq!(
+            report,
+            MergeReport {
+                added_labels: vec!["c"],
+                overwritten: vec![Overwritten {
+                    label: "a",
+                    language: Language::PL,
+                    old: "pl",
+                    new: "pl2",
+                }],
+                added_languages: vec![AddedLanguage {
+                    label: "b",
+                    language: Language::EN,
+                    text: "en",
+                }],
+                untouched_labels: vec!["d"],
+            }
+        );
+    }
+
+    #[test]
+    fn renders_merge_report() {
+        let base = vec![section("a", &[(Language::PL, "pl")])];
+        let merged = vec![section("a", &[(Language::PL, "pl2")])];
+        let report = MergeReport::new(&base, &merged);
+
+        assert_eq!(
+            report.render(ReportFormat::Text).unwrap(),
+            "Added labels: 0\nOverwritten translations: 1\nAdded languages: 0\n\
+            Untouched labels: 0\n~ a [PL]: \"pl\" -> \"pl2\"\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&report.render(ReportFormat::Json).unwrap())
+                .unwrap(),
+            serde_json::json!({
+                "added_labels": [],
+                "overwritten": [{"label": "a", "language": "PL", "old": "pl", "new": "pl2"}],
+                "added_languages": [],
+                "untouched_labels": [],
+            })
+        );
+    }
+
+    #[test]
+    fn merges_clientside_keeping_base_order() {
+        let base = vec![("B", "b"), ("A", ""), ("C", "c")];
+        let new = vec![("D", "d"), ("A", "a"), ("B", "b2")];
+
+        assert_eq!(
+            merge_clientside(base.clone(), new.clone(), MergeStrategy::Overwrite).unwrap(),
+            vec![("B", "b2"), ("A", "a"), ("C", "c"), ("D", "d")]
+        );
+        assert_eq!(
+            merge_clientside(base.clone(), new.clone(), MergeStrategy::FillGaps).unwrap(),
+            vec![("B", "b"), ("A", "a"), ("C", "c"), ("D", "d")]
+        );
+        assert_eq!(
+            merge_clientside(base.clone(), new.clone(), MergeStrategy::AddOnly).unwrap(),
+            vec![("B", "b"), ("A", ""), ("C", "c"), ("D", "d")]
+        );
+        assert!(matches!(
+            merge_clientside(base, new, MergeStrategy::PreferBase),
+            Err(MergeError::NoSourceLanguage)
+        ));
+    }
+
+    #[test]
+    fn merges_many_inputs_in_order() {
+        let inputs = vec![
+            vec![section("a", &[(Language::PL, "pl"), (Language::EN, "en")])],
+            vec![section("a", &[(Language::EN, "en2")])],
+            vec![
+                section("a", &[(Language::EN, "en2")]),
+                section("b", &[(Language::DE, "de")]),
+            ],
+            vec![section("a", &[(Language::PL, "pl3")])],
+        ];
+
+        let (merged, sources) = merge_many(inputs, MergeStrategy::Overwrite);
+
+        assert_eq!(
+            merged,
+            vec![
+                section("a", &[(Language::PL, "pl3"), (Language::EN, "en2")]),
+                section("b", &[(Language::DE, "de")]),
+            ]
+        );
+        assert_eq!(sources.get(&("a", Language::PL)), Some(&3));
+        assert_eq!(sources.get(&("a", Language::EN)), Some(&1));
+        assert_eq!(sources.get(&("b", Language::DE)), Some(&2));
+    }
+
+    #[test]
+    fn writes_sources_comment() {
+        let inputs = vec![
+            vec![section("a", &[(Language::PL, "pl"), (Language::EN, "en")])],
+            vec![section("a", &[(Language::EN, "en2")])],
+        ];
+
+        let (merged, sources) = merge_many(inputs, MergeStrategy::Overwrite);
+
+        assert_eq!(
+            sources_comment(&merged[0], &sources, &["base.txt", "vendor.txt"]).unwrap(),
+            "# sources: PL=base.txt, EN=vendor.txt"
+        );
+    }
+
+    #[test]
+    fn takes_changes_from_both_sides() {
+        let base = vec![section("a", &[(Language::PL, "pl"), (Language::EN, "en")])];
+        let ours = vec![section("a", &[(Language::PL, "pl2"), (Language::EN, "en")])];
+        let theirs = vec![
+            section("a", &[(Language::PL, "pl"), (Language::EN, "en2")]),
+            section("b", &[(Language::PL, "new")]),
+        ];
+
+        let merged = merge_three_way(&base, &ours, &theirs);
+
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(
+            merged.sections,
+            vec![
+                section("a", &[(Language::PL, "pl2"), (Language::EN, "en2")]),
+                section("b", &[(Language::PL, "new")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_conflict_and_keeps_ours() {
+        let base = vec![section("a", &[(Language::PL, "pl")])];
+        let ours = vec![section("a", &[(Language::PL, "ours")])];
+        let theirs = vec![section("a", &[(Language::PL, "theirs")])];
+
+        let merged = merge_three_way(&base, &ours, &theirs);
+
+        assert_eq!(merged.sections, ours);
+        assert_eq!(
+            merged.conflicts,
+            vec![Conflict {
+                label: "a",
+                language: Language::PL,
+                base: Some("pl"),
+                ours: Some("ours"),
+                theirs: Some("theirs"),
+            }]
+        );
+    }
+
+    #[test]
+    fn keeps_removal_of_untouched_label() {
+        let base = vec![section("a", &[(Language::PL, "pl")])];
+        let theirs = vec![section("a", &[(Language::PL, "pl")])];
+
+        assert!(merge_three_way(&base, &[], &theirs).sections.is_empty());
+    }
+
+    #[test]
+    fn keeps_removal_of_label_untouched_in_ours() {
+        let base = vec![
+            section("a", &[(Language::PL, "pl")]),
+            section("b", &[(Language::PL, "pl")]),
+        ];
+        let ours = base.clone();
+        let theirs = vec![section("b", &[(Language::PL, "pl")])];
+
+        let merged = merge_three_way(&base, &ours, &theirs);
+
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.sections, theirs);
+    }
+
+    #[test]
+    fn resolves_conflict_with_chosen_text() {
+        let base = vec![section("a", &[(Language::PL, "pl")])];
+        let ours = vec![section("a", &[(Language::PL, "ours")])];
+        let theirs = vec![section("a", &[(Language::PL, "theirs")])];
+
+        let mut merged = merge_three_way(&base, &ours, &theirs);
+        resolve_conflict(&mut merged.sections, &merged.conflicts[0], Some("theirs"));
+
+        assert_eq!(merged.sections, theirs);
+    }
+
+    #[test]
+    fn writes_conflict_markers() {
+        let base = vec![section("a", &[(Language::PL, "pl"), (Language::EN, "en")])];
+        let ours = vec![section(
+            "a",
+            &[(Language::PL, "ours"), (Language::EN, "en")],
+        )];
+        let theirs = vec![section(
+            "a",
+            &[(Language::PL, "theirs"), (Language::EN, "en")],
+        )];
+
+        let merged = merge_three_way(&base, &ours, &theirs);
+
+        assert_eq!(
+            generate_with_conflicts(&merged.sections, &merged.conflicts),
+            "section\n\tTXT\t\"a\"\n\tEN\t\"en\"\n\
+            <<<<<<< ours\n\tPL\t\"ours\"\n\
+            ||||||| base\n\tPL\t\"pl\"\n\
+            =======\n\tPL\t\"theirs\"\n\
+            >>>>>>> theirs\nend\n\n"
+        );
+    }
+
+    #[test]
+    fn keeps_label_deleted_by_ours_but_changed_by_theirs() {
+        let base = vec![section("a", &[(Language::PL, "pl"), (Language::EN, "en")])];
+        let theirs = vec![section(
+            "a",
+            &[(Language::PL, "theirs"), (Language::EN, "en")],
+        )];
+
+        let merged = merge_three_way(&base, &[], &theirs);
+
+        assert_eq!(merged.sections, vec![section("a", &[])]);
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].kind(), ConflictKind::DeletedByOurs);
+        assert_eq!(
+            generate_with_conflicts(&merged.sections, &merged.conflicts),
+            "section\n\tTXT\t\"a\"\n\
+            <<<<<<< ours (deleted)\n\
+            ||||||| base\n\tPL\t\"pl\"\n\
+            =======\n\tPL\t\"theirs\"\n\
+            >>>>>>> theirs\nend\n\n"
+        );
+
+        let merged = merge_three_way(&base, &theirs, &[]);
+        assert_eq!(
+            merged.sections,
+            vec![section("a", &[(Language::PL, "theirs")])]
+        );
+        assert_eq!(merged.conflicts[0].kind(), ConflictKind::DeletedByTheirs);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Located, Location};
+use crate::parse::ParseError;
+use crate::section::{borrowed_section, Language, Section};
+use serde::de::{DeserializeSeed, Error, MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Formatter;
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ModelError {
+    #[error("invalid {0}: {1}")]
+    Syntax(&'static str, String, Location),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl ModelError {
+    /// Sets the name of the file the error comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        match &mut self {
+            ModelError::Syntax(_, _, l) => l.set_file(file),
+            ModelError::Parse(_) | ModelError::Io(_) => {}
+        }
+
+        self
+    }
+}
+
+impl Located for ModelError {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            ModelError::Syntax(_, _, l) => Some(l),
+            ModelError::Parse(e) => e.location(),
+            ModelError::Io(_) => None,
+        }
+    }
+}
+
+impl From<ModelError> for Diagnostic {
+    fn from(error: ModelError) -> Self {
+        let location = error.location().cloned();
+        let code = match error {
+            ModelError::Syntax(..) => "model-syntax",
+            ModelError::Parse(e) => return e.into(),
+            ModelError::Io(e) => return ParseError::Io(e).into(),
+        };
+
+        Diagnostic::error(code, error.to_string())
+            .with_location(location)
+            .with_kind(DiagnosticKind::Parse)
+    }
+}
+
+fn syntax_error(format: &'static str, message: String, line: usize, column: usize) -> ModelError {
+    let location = Location {
+        line,
+        column,
+        length: 1,
+        ..Location::default()
+    };
+
+    ModelError::Syntax(format, message, location)
+}
+
+impl From<serde_json::Error> for ModelError {
+    fn from(error: serde_json::Error) -> Self {
+        syntax_error("JSON", error.to_string(), error.line(), error.column())
+    }
+}
+
+impl From<serde_yaml::Error> for ModelError {
+    fn from(error: serde_yaml::Error) -> Self {
+        let (line, column) = error.location().map_or((1, 1), |l| (l.line(), l.column()));
+
+        syntax_error("YAML", error.to_string(), line, column)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModelFormat {
+    Json,
+    Yaml,
+}
+
+impl ModelFormat {
+    /// Format matching the extension of the file, `.yaml` and `.yml` files are YAML.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("yaml") || e.eq_ignore_ascii_case("yml") => {
+                ModelFormat::Yaml
+            }
+            _ => ModelFormat::Json,
+        }
+    }
+}
+
+impl FromStr for ModelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ModelFormat::Json),
+            "yaml" => Ok(ModelFormat::Yaml),
+            f => Err(format!("invalid format {}, expected json or yaml", f)),
+        }
+    }
+}
+
+/// Sections serialized as a map from label to translations, keeping the order of the labels.
+struct SectionsRef<'s, 'a>(&'s [Section<'a>]);
+
+impl Serialize for SectionsRef<'_, '_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for section in self.0 {
+            map.serialize_entry(section.label, &section.translations)?;
+        }
+
+        map.end()
+    }
+}
+
+/// Translations read from JSON or YAML, by label in the order of the file.
+#[derive(Debug, Default, PartialEq)]
+pub struct SectionMap(pub Vec<(String, BTreeMap<Language, String>)>);
+
+impl SectionMap {
+    pub fn sections(&self) -> Vec<Section<'_>> {
+        self.0
+            .iter()
+            .map(|(label, translations)| {
+                borrowed_section(
+                    label,
+                    translations
+                        .iter()
+                        .map(|(lang, text)| (*lang, text.as_str())),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Translations of the label, each language may appear once and no text may hold a tab or a
+/// line break.
+struct TranslationsSeed<'l>(&'l str);
+
+impl<'de> DeserializeSeed<'de> for TranslationsSeed<'_> {
+    type Value = BTreeMap<Language, String>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for TranslationsSeed<'_> {
+    type Value = BTreeMap<Language, String>;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "a map from languages to translations")
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let mut translations = BTreeMap::new();
+        while let Some((lang, text)) = access.next_entry::<Language, String>()? {
+            if text.contains(['\t', '\n', '\r']) {
+                return Err(M::Error::custom(format!(
+                    "translation {} in label {} contains a tab or line break",
+                    lang, self.0
+                )));
+            }
+            if translations.insert(lang, text).is_some() {
+                return Err(M::Error::custom(format!(
+                    "language {} duplicate in label {}",
+                    lang, self.0
+                )));
+            }
+        }
+
+        Ok(translations)
+    }
+}
+
+struct SectionMapVisitor;
+
+impl<'de> Visitor<'de> for SectionMapVisitor {
+    type Value = SectionMap;
+
+    fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "a map from labels to maps from languages to translations"
+        )
+    }
+
+    fn visit_map<M>(self, mut access: M) -> Result<Self::Value, M::Error>
+    where
+        M: MapAccess<'de>,
+    {
+        let mut sections = vec![];
+        let mut labels = BTreeSet::new();
+        while let Some(label) = access.next_key::<String>()? {
+            // the label is written between quotes on a single line
+            if label.contains(['\t', '\n', '\r', '"']) {
+                return Err(M::Error::custom(format!(
+                    "label \"{}\" contains a tab, line break or quote",
+                    label.escape_debug()
+                )));
+            }
+            if !labels.insert(label.clone()) {
+                return Err(M::Error::custom(format!("label {} duplicate", label)));
+            }
+            let translations = access.next_value_seed(TranslationsSeed(&label))?;
+            sections.push((label, translations));
+        }
+
+        Ok(SectionMap(sections))
+    }
+}
+
+impl<'de> Deserialize<'de> for SectionMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(SectionMapVisitor)
+    }
+}
+
+/// Serializes sections as `label → {language → text}`.
+pub fn serialize_sections(sections: &[Section], format: ModelFormat) -> Result<String, ModelError> {
+    Ok(match format {
+        ModelFormat::Json => serde_json::to_string_pretty(&SectionsRef(sections))? + "\n",
+        ModelFormat::Yaml => serde_yaml::to_string(&SectionsRef(sections))?,
+    })
+}
+
+pub fn deserialize_sections(data: &str, format: ModelFormat) -> Result<SectionMap, ModelError> {
+    Ok(match format {
+        ModelFormat::Json => serde_json::from_str(data)?,
+        ModelFormat::Yaml => serde_yaml::from_str(data)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{deserialize_sections, serialize_sections, ModelError, ModelFormat};
+    use crate::parse::parse_data;
+    use crate::section::Language;
+
+    static FILE_STR: &str = "section\n\
+        \tTXT\t\"zeta\"\n\
+        \tPL\t\"Zażółć \"gęślą\" jaźń\"\n\
+        \tEN\t\"yes\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"123\"\n\
+        \tDE\t\"%d: Gold\"\n\
+        \tPL\t\"%d: złoto\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"alpha\"\n\
+        end\n";
+
+    #[test]
+    fn serializes_labels_in_file_order() {
+        let sections = parse_data(FILE_STR).unwrap();
+
+        assert_eq!(
+            serialize_sections(&sections, ModelFormat::Json).unwrap(),
+            "{\n  \"zeta\": {\n    \"PL\": \"Zażółć \\\"gęślą\\\" jaźń\",\n    \"EN\": \"yes\"\n  },\n  \
+            \"123\": {\n    \"PL\": \"%d: złoto\",\n    \"DE\": \"%d: Gold\"\n  },\n  \"alpha\": {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn round_trips_generated_sections() {
+        let sections = parse_data(FILE_STR).unwrap();
+
+        for format in [ModelFormat::Json, ModelFormat::Yaml] {
+            let serialized = serialize_sections(&sections, format).unwrap();
+            let map = deserialize_sections(&serialized, format).unwrap();
+
+            assert_eq!(map.sections(), sections);
+            let generated: Vec<String> = map.sections().iter().map(|s| s.generate()).collect();
+            let expected: Vec<String> = sections.iter().map(|s| s.generate()).collect();
+            assert_eq!(generated, expected);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_data() {
+        assert!(matches!(
+            deserialize_sections("{\n\"a\": {\"PL\": 1}}", ModelFormat::Json),
+            Err(ModelError::Syntax("JSON", _, l)) if l.line == 2
+        ));
+        assert!(matches!(
+            deserialize_sections("a:\n  pl-PL: text\n", ModelFormat::Yaml),
+            Err(ModelError::Syntax("YAML", message, _)) if message.contains("invalid language code")
+        ));
+        assert!(matches!(
+            deserialize_sections("{\"a\": {}, \"a\": {}}", ModelFormat::Json),
+            Err(ModelError::Syntax(_, message, _)) if message.contains("label a duplicate")
+        ));
+        assert!(matches!(
+            deserialize_sections("{\"a\\\"b\": {}}", ModelFormat::Json),
+            Err(ModelError::Syntax(_, message, _))
+                if message.contains("label \"a\\\"b\" contains a tab, line break or quote")
+        ));
+        assert!(matches!(
+            deserialize_sections("\"a\\tb\":\n  PL: text\n", ModelFormat::Yaml),
+            Err(ModelError::Syntax("YAML", message, _)) if message.contains("label \"a\\tb\"")
+        ));
+        assert!(matches!(
+            deserialize_sections("{\"a\": {\"PL\": \"Dwie\\nlinie\"}}", ModelFormat::Json),
+            Err(ModelError::Syntax(_, message, _))
+                if message.contains("translation PL in label a contains a tab or line break")
+        ));
+        assert!(matches!(
+            deserialize_sections("a:\n  PL: jeden\n  pl: dwa\n", ModelFormat::Yaml),
+            Err(ModelError::Syntax(_, message, _)) if message.contains("language PL duplicate in label a")
+        ));
+
+        let map = deserialize_sections("b:\n  en: text\n", ModelFormat::Yaml).unwrap();
+        assert_eq!(map.0[0].1[&Language::EN], "text");
+    }
+}
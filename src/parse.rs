@@ -1,28 +1,43 @@
-use std::fs::read_to_string;
 use std::path::Path;
 
 use thiserror::Error;
 
-use crate::section::Language::{EN, PL};
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Located, Location};
+use crate::encoding::read_encoded;
+use crate::merge::MergeStrategy;
 use crate::section::{Language, Section};
 use std::collections::BTreeMap;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum KeywordActions {
     NewSection,
     Label,
     Translation(Language),
 }
 
-static KEYWORDS: [(&str, KeywordActions); 4] = [
+static KEYWORDS: [(&str, KeywordActions); 2] = [
     ("section", KeywordActions::NewSection),
     ("TXT", KeywordActions::Label),
-    ("PL", KeywordActions::Translation(PL)),
-    ("EN", KeywordActions::Translation(EN)),
 ];
 
+/// Resolves the keyword starting the line. Any other upper-case keyword names a language.
+fn keyword_action(keyword: &str) -> Option<KeywordActions> {
+    for (k, action) in KEYWORDS.iter() {
+        if *k == keyword {
+            return Some(*action);
+        }
+    }
+
+    if Language::is_code(keyword) {
+        return Some(KeywordActions::Translation(keyword.into()));
+    }
+
+    None
+}
+
+/// Reads a file in its detected encoding.
 pub fn read_file<T: AsRef<Path>>(filepath: T) -> std::io::Result<String> {
-    read_to_string(filepath)
+    read_encoded(filepath, None).map(|(data, _)| data)
 }
 
 pub fn omit_line<T>(line: T) -> bool
@@ -32,12 +47,17 @@ where
     line.as_ref().starts_with("#") || line.as_ref().is_empty()
 }
 
-fn extract_text<'a, T: ?Sized>(text: &'a T, key: &'a T) -> Option<&'a str>
+/// Keyword starting the trimmed line, e.g. `section`, `TXT` or a language code.
+pub fn line_keyword(line: &str) -> &str {
+    line.split(['\t', ' ']).next().unwrap_or("")
+}
+
+pub fn extract_text<'a, T>(text: &'a T, key: &'a T) -> Option<&'a str>
 where
-    T: AsRef<str>,
+    T: AsRef<str> + ?Sized,
 {
     let elements = text.as_ref().split("\t").collect::<Vec<&str>>();
-    if match elements.get(0) {
+    if match elements.first() {
         Some(v) => v.starts_with(key.as_ref()),
         None => return None,
     } {
@@ -56,111 +76,367 @@ where
 #[derive(Error, Debug)]
 pub enum ParseError {
     #[error("invalid syntax near {0}\t{1}")]
-    Syntax(String, String),
+    Syntax(String, String, Location),
     #[error("empty or invalid line near {0}")]
-    Empty(String),
+    Empty(String, Location),
     #[error("language {0} already exists in label {1}")]
-    LanguageDuplicate(Language, String),
+    LanguageDuplicate(Language, String, Location),
     #[error("label {0} duplicate")]
-    LabelDuplicate(String),
-    #[error("argument count in label {0} is not equal everywhere")]
-    ArgumentMismatch(String),
+    LabelDuplicate(String, Location),
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
 
-pub fn parse_data<'a, T: ?Sized>(data: &'a T) -> Result<Vec<Section<'a>>, ParseError>
+impl ParseError {
+    /// Sets the name of the file the error comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        match &mut self {
+            ParseError::Syntax(_, _, l)
+            | ParseError::Empty(_, l)
+            | ParseError::LanguageDuplicate(_, _, l)
+            | ParseError::LabelDuplicate(_, l) => l.set_file(file),
+            ParseError::Io(_) => {}
+        }
+
+        self
+    }
+}
+
+impl Located for ParseError {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            ParseError::Syntax(_, _, l)
+            | ParseError::Empty(_, l)
+            | ParseError::LanguageDuplicate(_, _, l)
+            | ParseError::LabelDuplicate(_, l) => Some(l),
+            ParseError::Io(_) => None,
+        }
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let location = error.location().cloned();
+        let message = error.to_string();
+        let diagnostic = match &error {
+            ParseError::Syntax(..) => Diagnostic::error("syntax", message),
+            ParseError::Empty(..) => Diagnostic::error("empty-line", message),
+            ParseError::LanguageDuplicate(lang, label, _) => {
+                Diagnostic::error("duplicate-language", message)
+                    .with_label(label)
+                    .with_language(*lang)
+            }
+            ParseError::LabelDuplicate(label, _) => {
+                Diagnostic::error("duplicate-label", message).with_label(label)
+            }
+            ParseError::Io(_) => {
+                return Diagnostic::error("io", message).with_kind(DiagnosticKind::Io)
+            }
+        };
+
+        diagnostic
+            .with_location(location)
+            .with_kind(DiagnosticKind::Parse)
+    }
+}
+
+/// Sections parsed from a file together with every problem found on the way.
+#[derive(Debug, Default)]
+pub struct ParsedData<'a> {
+    pub sections: Vec<Section<'a>>,
+    /// Location of the label of each section, in the same order as `sections`.
+    pub locations: Vec<Location>,
+    /// Location of the text of each translation, in the same order as `sections`.
+    pub translation_locations: Vec<BTreeMap<Language, Location>>,
+    pub errors: Vec<ParseError>,
+    /// Language codes first seen in this file, which may be typos of a known one.
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl ParsedData<'_> {
+    /// Sets the name of the file every error and location comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        for l in self
+            .locations
+            .iter_mut()
+            .chain(
+                self.translation_locations
+                    .iter_mut()
+                    .flat_map(|t| t.values_mut()),
+            )
+            .chain(self.warnings.iter_mut().filter_map(|d| d.location.as_mut()))
+        {
+            l.set_file(&file);
+        }
+        self.errors = self
+            .errors
+            .into_iter()
+            .map(|e| e.with_file(&file))
+            .collect();
+
+        self
+    }
+}
+
+pub fn parse_data<'a, T>(data: &'a T) -> Result<Vec<Section<'a>>, ParseError>
 where
-    T: AsRef<str>,
+    T: AsRef<str> + ?Sized,
+{
+    let mut parsed = parse_data_recovering(data);
+    if parsed.errors.is_empty() {
+        Ok(parsed.sections)
+    } else {
+        Err(parsed.errors.remove(0))
+    }
+}
+
+/// Parses the whole file, skipping every section which contains an error instead of stopping
+/// at the first one.
+pub fn parse_data_recovering<'a, T>(data: &'a T) -> ParsedData<'a>
+where
+    T: AsRef<str> + ?Sized,
 {
-    let mut v: Vec<Section> = vec![];
+    let mut parsed = ParsedData::default();
+    let mut skip_section = false;
 
-    for x in data.as_ref().lines().map(|l| l.trim()) {
+    for (i, raw) in data.as_ref().lines().enumerate() {
+        let x = raw.trim();
         if omit_line(x) {
             continue;
         }
 
-        for (keyword, action) in KEYWORDS.iter() {
-            if x.starts_with(keyword) {
-                if *action == KeywordActions::NewSection {
-                    v.push(Section::new());
-                    continue;
-                }
-
-                let e = extract_text(x, keyword).ok_or(ParseError::Empty(x.to_owned()))?;
-                let last = v
-                    .last_mut()
-                    .ok_or(ParseError::Syntax(keyword.to_string(), e.to_owned()))?;
-
-                match *action {
-                    KeywordActions::Label => {
-                        last.label = e;
-                    }
-                    KeywordActions::Translation(lang) => {
-                        if last.translations.contains_key(&lang) {
-                            return Err(ParseError::LanguageDuplicate(lang, last.label.to_owned()));
-                        }
-                        if let Some(_) = last.translations.insert(lang, e) {
-                            return Err(ParseError::LabelDuplicate(last.label.to_owned()));
-                        }
-                    }
-                    _ => {}
-                }
+        let keyword = line_keyword(x);
+        let registered = Language::is_registered(keyword);
+        let action = match keyword_action(keyword) {
+            Some(action) => action,
+            None => continue,
+        };
+
+        if let (KeywordActions::Translation(lang), false) = (action, registered) {
+            parsed.warnings.push(
+                Diagnostic::warning(
+                    "unknown-language",
+                    format!(
+                        "language {} is not a default language nor one given on the command line",
+                        lang
+                    ),
+                )
+                .with_language(lang)
+                .with_location(Some(Location::in_line(i, raw, keyword))),
+            );
+        }
+
+        if action == KeywordActions::NewSection {
+            parsed.sections.push(Section::new());
+            parsed.locations.push(Location::in_line(i, raw, keyword));
+            parsed.translation_locations.push(BTreeMap::new());
+            skip_section = false;
+            continue;
+        }
+
+        if skip_section {
+            continue;
+        }
+
+        if let Err(e) = parse_line(&mut parsed, action, keyword, i, raw) {
+            if parsed.sections.pop().is_some() {
+                parsed.locations.pop();
+                parsed.translation_locations.pop();
+            }
+            parsed.errors.push(e);
+            skip_section = true;
+        }
+    }
+
+    parsed
+}
+
+fn parse_line<'a>(
+    parsed: &mut ParsedData<'a>,
+    action: KeywordActions,
+    keyword: &'a str,
+    i: usize,
+    raw: &'a str,
+) -> Result<(), ParseError> {
+    let x = raw.trim();
+    let e = extract_text(x, keyword)
+        .ok_or_else(|| ParseError::Empty(x.to_owned(), Location::in_line(i, raw, x)))?;
+    let last = parsed.sections.last_mut().ok_or_else(|| {
+        ParseError::Syntax(
+            keyword.to_string(),
+            e.to_owned(),
+            Location::in_line(i, raw, keyword),
+        )
+    })?;
+
+    match action {
+        KeywordActions::Label => {
+            last.label = e;
+            if let Some(l) = parsed.locations.last_mut() {
+                *l = Location::in_line(i, raw, e);
+            }
+        }
+        KeywordActions::Translation(lang) => {
+            if last.translations.contains_key(&lang) {
+                return Err(ParseError::LanguageDuplicate(
+                    lang,
+                    last.label.to_owned(),
+                    Location::in_line(i, raw, keyword),
+                ));
+            }
+            if last.translations.insert(lang, e).is_some() {
+                return Err(ParseError::LabelDuplicate(
+                    last.label.to_owned(),
+                    Location::in_line(i, raw, keyword),
+                ));
+            }
+            if let Some(t) = parsed.translation_locations.last_mut() {
+                t.insert(lang, Location::in_line(i, raw, e));
             }
         }
+        _ => {}
     }
 
-    Ok(v)
+    Ok(())
 }
 
-pub fn merge_sections<'a>(mut base: Vec<Section<'a>>, new: Vec<Section<'a>>) -> Vec<Section<'a>> {
-    for mut x in new {
-        let pos = base.iter_mut().find(|f| f.label == x.label);
-        if let Some(elem) = pos {
-            for new_t in x.translations.iter_mut() {
-                if let Some(old_t) = elem.translations.iter_mut().find(|(t, _)| t == &new_t.0) {
-                    *old_t.1 = *new_t.1;
-                } else {
-                    elem.translations.insert(*new_t.0, new_t.1);
-                }
+/// Whether the translation `lang` of the section is a copy of another of its languages. The
+/// source text, the first language of the section, is never a copy.
+fn copied_from_other_language(lang: Language, text: &str, section: &Section) -> bool {
+    let source = section.translations.keys().next();
+
+    source != Some(&lang)
+        && section
+            .translations
+            .iter()
+            .any(|(other, other_text)| *other != lang && *other_text == text)
+}
+
+pub fn merge_sections<'a>(
+    mut base: Vec<Section<'a>>,
+    new: Vec<Section<'a>>,
+    strategy: MergeStrategy,
+) -> Vec<Section<'a>> {
+    for x in new {
+        let elem = match base.iter_mut().find(|f| f.label == x.label) {
+            Some(elem) => elem,
+            None => {
+                base.push(x);
+                continue;
+            }
+        };
+
+        if strategy == MergeStrategy::AddOnly {
+            continue;
+        }
+
+        for (lang, text) in x.translations.iter() {
+            let keep_base = match strategy {
+                MergeStrategy::FillGaps => true,
+                MergeStrategy::PreferBase => copied_from_other_language(*lang, text, &x),
+                _ => false,
+            };
+
+            if !keep_base || !elem.translations.contains_key(lang) {
+                elem.translations.insert(*lang, text);
             }
-        } else {
-            base.push(x)
         }
     }
 
     base
 }
 
-pub fn parse_clientside<T>(data: T) -> Result<BTreeMap<String, String>, ParseError>
+/// Entries of a client-side file in file order together with every problem found on the way.
+#[derive(Debug, Default)]
+pub struct ParsedClientside<'a> {
+    pub entries: Vec<(&'a str, &'a str)>,
+    /// Location of the label of each entry, in the same order as `entries`.
+    pub locations: Vec<Location>,
+    pub errors: Vec<ParseError>,
+}
+
+impl<'a> ParsedClientside<'a> {
+    /// Sets the name of the file every error and location comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        for l in self.locations.iter_mut() {
+            l.set_file(&file);
+        }
+        self.errors = self
+            .errors
+            .into_iter()
+            .map(|e| e.with_file(&file))
+            .collect();
+
+        self
+    }
+
+    /// Translations by label.
+    pub fn map(&self) -> BTreeMap<&'a str, &'a str> {
+        self.entries.iter().copied().collect()
+    }
+
+    /// Location of the label in the file.
+    pub fn location(&self, label: &str) -> Option<&Location> {
+        let pos = self.entries.iter().position(|(l, _)| *l == label)?;
+        self.locations.get(pos)
+    }
+}
+
+/// Parses a client-side file keeping the order of its labels.
+pub fn parse_clientside_entries<T>(data: &T) -> Result<Vec<(&str, &str)>, ParseError>
 where
-    T: AsRef<str>,
+    T: AsRef<str> + ?Sized,
 {
-    let mut map = BTreeMap::new();
+    let mut parsed = parse_clientside_recovering(data);
+    if parsed.errors.is_empty() {
+        Ok(parsed.entries)
+    } else {
+        Err(parsed.errors.remove(0))
+    }
+}
 
-    for x in data.as_ref().lines().map(|l| l.trim()) {
+/// Parses the whole client-side file, keeping the first translation of duplicated labels.
+/// Everything after the first tab of a line is its translation.
+pub fn parse_clientside_recovering<T>(data: &T) -> ParsedClientside<'_>
+where
+    T: AsRef<str> + ?Sized,
+{
+    let mut parsed = ParsedClientside::default();
+
+    for (i, raw) in data.as_ref().lines().enumerate() {
+        let x = raw.trim();
         if omit_line(x) {
             continue;
         }
 
-        let split: Vec<&str> = x.split("\t").collect();
-        let (label_slice, translation_slice) = split.split_at(1);
-        let label = label_slice.join("");
-        if let Some(_) = map.insert(label, translation_slice.join("")) {
-            return Err(ParseError::LabelDuplicate(label_slice.join("")));
+        let (label, translation) = x.split_once('\t').unwrap_or((x, ""));
+        let location = Location::in_line(i, raw, label);
+        if parsed.entries.iter().any(|(l, _)| *l == label) {
+            parsed
+                .errors
+                .push(ParseError::LabelDuplicate(label.to_owned(), location));
+            continue;
         }
+        parsed.entries.push((label, translation));
+        parsed.locations.push(location);
     }
 
-    Ok(map)
+    parsed
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::BTreeMap;
 
-    use crate::parse::{extract_text, merge_sections, omit_line, parse_data, ParseError};
-    use crate::section::Language::{EN, PL};
+    use crate::diagnostic::{Located, Location};
+    use crate::merge::MergeStrategy;
+    use crate::parse::{
+        extract_text, merge_sections, omit_line, parse_clientside_entries,
+        parse_clientside_recovering, parse_data, parse_data_recovering, ParseError,
+    };
+    use crate::section::Language;
     use crate::section::Section;
+    use crate::test_support::section;
 
     use super::read_file;
 
@@ -187,8 +463,8 @@ mod tests {
             label: "asd2",
             translations: BTreeMap::new(),
         };
-        s1.translations.insert(PL, "pl1");
-        s2.translations.insert(PL, "pl2");
+        s1.translations.insert(Language::PL, "pl1");
+        s2.translations.insert(Language::PL, "pl2");
 
         let v1 = vec![s1];
         let v2 = vec![s2];
@@ -204,12 +480,12 @@ mod tests {
             translations: BTreeMap::new(),
         };
 
-        res1.translations.insert(PL, "pl1");
-        res2.translations.insert(PL, "pl2");
+        res1.translations.insert(Language::PL, "pl1");
+        res2.translations.insert(Language::PL, "pl2");
 
         let res_vec = vec![res1, res2];
 
-        assert_eq!(merge_sections(v1, v2), res_vec);
+        assert_eq!(merge_sections(v1, v2, MergeStrategy::Overwrite), res_vec);
     }
 
     #[test]
@@ -222,9 +498,9 @@ mod tests {
             label: "asd",
             translations: BTreeMap::new(),
         };
-        s1.translations.insert(PL, "pl1");
-        s1.translations.insert(EN, "en1");
-        s2.translations.insert(PL, "pl2");
+        s1.translations.insert(Language::PL, "pl1");
+        s1.translations.insert(Language::EN, "en1");
+        s2.translations.insert(Language::PL, "pl2");
 
         let v1 = vec![s1];
         let v2 = vec![s2];
@@ -236,37 +512,134 @@ mod tests {
             translations: BTreeMap::new(),
         };
 
-        res1.translations.insert(EN, "en1");
-        res1.translations.insert(PL, "pl2");
+        res1.translations.insert(Language::EN, "en1");
+        res1.translations.insert(Language::PL, "pl2");
 
         let res_vec = vec![res1];
 
-        assert_eq!(merge_sections(v1, v2), res_vec);
+        assert_eq!(merge_sections(v1, v2, MergeStrategy::Overwrite), res_vec);
+    }
+
+    fn strategy_inputs() -> (Vec<Section<'static>>, Vec<Section<'static>>) {
+        let base = vec![
+            section("a", &[(Language::PL, "pl"), (Language::EN, "en")]),
+            section("b", &[(Language::PL, "pl")]),
+        ];
+        let new = vec![
+            section("a", &[(Language::PL, "en2"), (Language::EN, "en2")]),
+            section("b", &[(Language::PL, "pl2"), (Language::EN, "en")]),
+            section("c", &[(Language::PL, "new")]),
+        ];
+        (base, new)
+    }
+
+    #[test]
+    fn add_only_keeps_existing_labels() {
+        let (base, new) = strategy_inputs();
+
+        assert_eq!(
+            merge_sections(base, new, MergeStrategy::AddOnly),
+            vec![
+                section("a", &[(Language::PL, "pl"), (Language::EN, "en")]),
+                section("b", &[(Language::PL, "pl")]),
+                section("c", &[(Language::PL, "new")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn fill_gaps_adds_missing_languages() {
+        let (base, new) = strategy_inputs();
+
+        assert_eq!(
+            merge_sections(base, new, MergeStrategy::FillGaps),
+            vec![
+                section("a", &[(Language::PL, "pl"), (Language::EN, "en")]),
+                section("b", &[(Language::PL, "pl"), (Language::EN, "en")]),
+                section("c", &[(Language::PL, "new")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn overwrite_takes_newer_translations() {
+        let (base, new) = strategy_inputs();
+
+        assert_eq!(
+            merge_sections(base, new, MergeStrategy::Overwrite),
+            vec![
+                section("a", &[(Language::PL, "en2"), (Language::EN, "en2")]),
+                section("b", &[(Language::PL, "pl2"), (Language::EN, "en")]),
+                section("c", &[(Language::PL, "new")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefer_base_skips_copied_translations() {
+        let (base, new) = strategy_inputs();
+
+        assert_eq!(
+            merge_sections(base, new, MergeStrategy::PreferBase),
+            vec![
+                section("a", &[(Language::PL, "en2"), (Language::EN, "en")]),
+                section("b", &[(Language::PL, "pl2"), (Language::EN, "en")]),
+                section("c", &[(Language::PL, "new")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefer_base_skips_copies_of_any_language() {
+        let base = vec![section(
+            "a",
+            &[(Language::PL, "Miecz"), (Language::DE, "Schwert")],
+        )];
+        let new = vec![section(
+            "a",
+            &[
+                (Language::PL, "Miecz"),
+                (Language::EN, "Sword"),
+                (Language::DE, "Sword"),
+            ],
+        )];
+
+        assert_eq!(
+            merge_sections(base, new, MergeStrategy::PreferBase),
+            vec![section(
+                "a",
+                &[
+                    (Language::PL, "Miecz"),
+                    (Language::EN, "Sword"),
+                    (Language::DE, "Schwert")
+                ]
+            )]
+        );
     }
 
     #[test]
     fn omit_line_if_empty() {
-        assert_eq!(omit_line(""), true);
+        assert!(omit_line(""));
     }
 
     #[test]
     fn omit_line_if_commented() {
-        assert_eq!(omit_line("#test"), true);
+        assert!(omit_line("#test"));
     }
 
     #[test]
     fn not_omit_line_if_normal() {
-        assert_eq!(omit_line("PL\t\"Hello, world!\""), false);
+        assert!(!omit_line("PL\t\"Hello, world!\""));
     }
 
     #[test]
     fn reads_file() {
-        assert_eq!(read_file("Cargo.toml").is_ok(), true)
+        assert!(read_file("Cargo.toml").is_ok())
     }
 
     #[test]
     fn error_if_not_found_file() {
-        assert_eq!(read_file("hello.world").is_err(), true)
+        assert!(read_file("hello.world").is_err())
     }
 
     #[test]
@@ -292,15 +665,50 @@ mod tests {
         sections[0].label = "s1";
         sections[1].label = "s2";
 
-        sections[0].translations.insert(PL, "pl1");
-        sections[0].translations.insert(EN, "en1");
+        sections[0].translations.insert(Language::PL, "pl1");
+        sections[0].translations.insert(Language::EN, "en1");
 
-        sections[1].translations.insert(PL, "pl2");
-        sections[1].translations.insert(EN, "en2");
+        sections[1].translations.insert(Language::PL, "pl2");
+        sections[1].translations.insert(Language::EN, "en2");
 
         assert_eq!(res.unwrap(), sections);
     }
 
+    #[test]
+    fn parses_any_language() {
+        let res = parse_data(
+            "section\n\
+            TXT	\"s1\"\n\
+            DE	\"de1\"\n\
+            KR	\"kr1\"\n\
+        end",
+        )
+        .unwrap();
+
+        assert_eq!(res[0].translations.get(&Language::DE), Some(&"de1"));
+        assert_eq!(res[0].translations.get(&Language::from("KR")), Some(&"kr1"));
+    }
+
+    #[test]
+    fn warns_once_about_unknown_language() {
+        let p = parse_data_recovering(
+            "section\n\
+            TXT	\"s1\"\n\
+            PL	\"pl1\"\n\
+            QZ	\"qz1\"\n\
+        end\n\
+        section\n\
+            TXT	\"s2\"\n\
+            QZ	\"qz2\"\n\
+        end",
+        );
+
+        assert!(p.errors.is_empty());
+        assert_eq!(p.warnings.len(), 1);
+        assert_eq!(p.warnings[0].code, "unknown-language");
+        assert_eq!(p.warnings[0].location.as_ref().unwrap().line, 4);
+    }
+
     #[test]
     fn error_if_duplicate() {
         let p = parse_data(
@@ -312,7 +720,82 @@ mod tests {
         );
 
         assert!(match p {
-            Err(ParseError::LanguageDuplicate(PL, label)) => label == "s1".to_owned(),
+            Err(ParseError::LanguageDuplicate(Language::PL, label, _)) => label == "s1",
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn recovers_after_broken_section() {
+        let p = parse_data_recovering(
+            "section\n\
+            TXT	\"s1\"\n\
+            PL	\"pl1\"\n\
+            PL	\"pl2\"\n\
+            EN	\"en1\"\n\
+        end\n\
+        section\n\
+            TXT	\"s2\"\n\
+            PL	\"pl1\"\n\
+            PL	\"pl2\"\n\
+        end\n\
+        section\n\
+            TXT	\"s3\"\n\
+            PL	\"pl3\"\n\
+        end",
+        );
+
+        assert_eq!(p.errors.len(), 2);
+        assert_eq!(p.sections.len(), 1);
+        assert_eq!(p.sections[0].label, "s3");
+        assert_eq!(p.locations[0].line, 13);
+        assert_eq!(p.translation_locations.len(), 1);
+        assert_eq!(p.translation_locations[0][&Language::PL].line, 14);
+        assert_eq!(p.translation_locations[0][&Language::PL].column, 5);
+    }
+
+    #[test]
+    fn error_contains_location() {
+        let p = parse_data(
+            "section\n\
+            \tTXT\t\"s1\"\n\
+            \tPL\t\"pl1\"\n\
+            \tPL\t\"pl2\"\n\
+        end",
+        );
+        let location = p
+            .unwrap_err()
+            .with_file("locale_string.txt")
+            .location()
+            .cloned();
+
+        assert_eq!(
+            location,
+            Some(Location {
+                file: "locale_string.txt".to_owned(),
+                line: 4,
+                column: 2,
+                length: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_clientside_entries_in_order() {
+        let entries = parse_clientside_entries("B\tb\n#c\nA\ta\tx\nC");
+
+        assert_eq!(entries.unwrap(), vec![("B", "b"), ("A", "a\tx"), ("C", "")]);
+    }
+
+    #[test]
+    fn clientside_duplicate_contains_location() {
+        let parsed = parse_clientside_recovering("A\ta\n\nA\tb\tc");
+
+        assert_eq!(parsed.entries, vec![("A", "a")]);
+        assert_eq!(parsed.location("A").map(|l| l.line), Some(1));
+        assert!(parsed.location("B").is_none());
+        assert!(match parsed.errors.as_slice() {
+            [ParseError::LabelDuplicate(label, l)] => label == "A" && l.line == 3,
             _ => false,
         })
     }
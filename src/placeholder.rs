@@ -0,0 +1,181 @@
+/// Flags allowed between `%` and the width.
+static FLAGS: &str = "-+ #0'";
+
+/// Length modifiers, longest first so `ll` wins over `l`.
+static LENGTHS: [&str; 9] = ["hh", "ll", "h", "l", "L", "q", "j", "z", "t"];
+
+/// Conversion specification of a printf format string, e.g. `%-10s`, `%.2f` or `%lld`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placeholder<'a> {
+    /// Whole specification including the `%`.
+    pub spec: &'a str,
+    /// Byte offset of the `%` in the text.
+    pub offset: usize,
+    /// Number of `*` in the width and precision, each of them consumes an `int` argument.
+    pub stars: usize,
+    pub length: &'a str,
+    pub conversion: char,
+}
+
+impl Placeholder<'_> {
+    /// Type of the argument passed for the conversion. Conversions reading the same C type,
+    /// like `%d` and `%i` or `%u` and `%x`, are treated as equal.
+    pub fn argument_type(&self) -> String {
+        let class = match self.conversion {
+            'd' | 'i' => 'd',
+            'u' | 'o' | 'x' | 'X' => 'u',
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' | 'a' | 'A' => 'f',
+            c => c,
+        };
+        let length = match self.length {
+            "q" => "ll",
+            l => l,
+        };
+
+        format!("{}{}", length, class)
+    }
+}
+
+/// Parses the specification starting at the `%` at `offset`. `None` means the `%` does not
+/// start a valid conversion and is just text.
+fn parse_placeholder(text: &str, offset: usize) -> Option<Placeholder<'_>> {
+    let rest = &text[offset + 1..];
+    let mut i = rest.len() - rest.trim_start_matches(|c| FLAGS.contains(c)).len();
+    let mut stars = 0;
+
+    let mut skip_number = |i: &mut usize| {
+        if rest[*i..].starts_with('*') {
+            stars += 1;
+            *i += 1;
+        } else {
+            *i += rest[*i..].len()
+                - rest[*i..]
+                    .trim_start_matches(|c: char| c.is_ascii_digit())
+                    .len();
+        }
+    };
+
+    skip_number(&mut i);
+    if rest[i..].starts_with('.') {
+        i += 1;
+        skip_number(&mut i);
+    }
+
+    let length = LENGTHS
+        .iter()
+        .find(|l| rest[i..].starts_with(*l))
+        .copied()
+        .unwrap_or("");
+    i += length.len();
+
+    let conversion = rest[i..].chars().next()?;
+    if !"diouxXeEfFgGaAcspn".contains(conversion) {
+        return None;
+    }
+    let end = offset + 1 + i + conversion.len_utf8();
+
+    Some(Placeholder {
+        spec: &text[offset..end],
+        offset,
+        stars,
+        length,
+        conversion,
+    })
+}
+
+/// Every conversion specification in the text, in order. `%%` is a literal percent sign
+/// and not a placeholder.
+pub fn placeholders(text: &str) -> Vec<Placeholder<'_>> {
+    let mut found = vec![];
+    let mut offset = 0;
+
+    while let Some(position) = text[offset..].find('%') {
+        let start = offset + position;
+        if text[start + 1..].starts_with('%') {
+            offset = start + 2;
+            continue;
+        }
+
+        match parse_placeholder(text, start) {
+            Some(placeholder) => {
+                offset = start + placeholder.spec.len();
+                found.push(placeholder);
+            }
+            None => offset = start + 1,
+        }
+    }
+
+    found
+}
+
+/// Types of the arguments the text consumes, in order, including `*` width and precision.
+pub fn argument_types(text: &str) -> Vec<String> {
+    placeholders(text)
+        .iter()
+        .flat_map(|p| {
+            std::iter::repeat("d".to_string())
+                .take(p.stars)
+                .chain(std::iter::once(p.argument_type()))
+        })
+        .collect()
+}
+
+/// Argument types as printf conversions, e.g. `%d, %s`, or `none` when there are none.
+pub fn format_argument_types(types: &[String]) -> String {
+    if types.is_empty() {
+        return "none".to_string();
+    }
+
+    types
+        .iter()
+        .map(|t| format!("%{}", t))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::placeholder::{argument_types, format_argument_types, placeholders};
+
+    #[test]
+    fn tokenizes_conversion_specifications() {
+        let specs: Vec<&str> = placeholders("%u %lld %5d %-10s %.2f %c %x %qd")
+            .iter()
+            .map(|p| p.spec)
+            .collect();
+
+        assert_eq!(
+            specs,
+            vec!["%u", "%lld", "%5d", "%-10s", "%.2f", "%c", "%x", "%qd"]
+        );
+    }
+
+    #[test]
+    fn skips_literal_percent_signs() {
+        let found = placeholders("100%% done, %%d is not a placeholder but %d is");
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].spec, "%d");
+        assert_eq!(found[0].offset, 41);
+        assert!(placeholders("50% ").is_empty());
+        assert!(placeholders("trailing %").is_empty());
+    }
+
+    #[test]
+    fn compares_argument_types() {
+        assert_eq!(argument_types("%5d %i"), argument_types("%d %d"));
+        assert_eq!(argument_types("%*d"), vec!["d", "d"]);
+        assert_eq!(argument_types("%lld %qd %ld"), vec!["lld", "lld", "ld"]);
+        assert_ne!(argument_types("%s %d"), argument_types("%d %s"));
+        assert_ne!(argument_types("%d"), argument_types("%ld"));
+    }
+
+    #[test]
+    fn formats_argument_types() {
+        assert_eq!(
+            format_argument_types(&argument_types("%*d %s")),
+            "%d, %d, %s"
+        );
+        assert_eq!(format_argument_types(&argument_types("no args")), "none");
+    }
+}
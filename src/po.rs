@@ -0,0 +1,466 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Located, Location};
+use crate::parse::ParseError;
+use crate::placeholder::placeholders;
+use crate::section::{borrowed_section, Language, Section};
+use crate::syntax::Document;
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PoError {
+    #[error("invalid PO syntax near {0}")]
+    Syntax(String, Location),
+    #[error("PO header does not name a valid language, found {0:?}")]
+    Language(String, Location),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl PoError {
+    /// Sets the name of the file the error comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        match &mut self {
+            PoError::Syntax(_, l) | PoError::Language(_, l) => l.set_file(file),
+            PoError::Parse(_) | PoError::Io(_) => {}
+        }
+
+        self
+    }
+}
+
+impl Located for PoError {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            PoError::Syntax(_, l) | PoError::Language(_, l) => Some(l),
+            PoError::Parse(e) => e.location(),
+            PoError::Io(_) => None,
+        }
+    }
+}
+
+impl From<PoError> for Diagnostic {
+    fn from(error: PoError) -> Self {
+        let location = error.location().cloned();
+        let code = match error {
+            PoError::Syntax(..) => "po-syntax",
+            PoError::Language(..) => "po-language",
+            PoError::Parse(e) => return e.into(),
+            PoError::Io(e) => return ParseError::Io(e).into(),
+        };
+
+        Diagnostic::error(code, error.to_string())
+            .with_location(location)
+            .with_kind(DiagnosticKind::Parse)
+    }
+}
+
+/// Escapes text for a quoted PO string.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn header(lang: Option<Language>) -> String {
+    let language = lang
+        .map(|l| l.code().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    format!(
+        "msgid \"\"\n\
+        msgstr \"\"\n\
+        \"Language: {}\\n\"\n\
+        \"MIME-Version: 1.0\\n\"\n\
+        \"Content-Type: text/plain; charset=UTF-8\\n\"\n\
+        \"Content-Transfer-Encoding: 8bit\\n\"\n",
+        language
+    )
+}
+
+/// Generates a PO file translating `source` into `lang`, or the POT template when `lang` is
+/// `None`. Labels become `msgctxt`, source texts `msgid`, and comments written in the document
+/// above or inside a section become translator comments. Labels without a source text use
+/// the label itself as `msgid`, an empty `msgid` is reserved for the header.
+pub fn generate_po(
+    sections: &[Section],
+    document: &Document,
+    source: Language,
+    lang: Option<Language>,
+) -> String {
+    let comments: BTreeMap<&str, Vec<&str>> = document
+        .sections()
+        .filter_map(|s| Some((s.label()?, s.comments())))
+        .collect();
+    let mut po = header(lang);
+
+    for section in sections {
+        let msgid = section
+            .translations
+            .get(&source)
+            .copied()
+            .filter(|t| !t.is_empty())
+            .unwrap_or(section.label);
+        let msgstr = lang
+            .and_then(|l| section.translations.get(&l))
+            .copied()
+            .unwrap_or("");
+
+        po.push('\n');
+        for comment in comments.get(section.label).into_iter().flatten() {
+            po.push_str(&format!("# {}\n", comment));
+        }
+        if !placeholders(msgid).is_empty() || !placeholders(msgstr).is_empty() {
+            po.push_str("#, c-format\n");
+        }
+        po.push_str(&format!(
+            "msgctxt \"{}\"\nmsgid \"{}\"\nmsgstr \"{}\"\n",
+            escape(section.label),
+            escape(msgid),
+            escape(msgstr)
+        ));
+    }
+
+    po
+}
+
+/// Single message of a PO file with its strings unescaped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoEntry {
+    pub context: Option<String>,
+    pub msgid: String,
+    pub msgstr: String,
+    pub fuzzy: bool,
+    /// Entries with `msgid_plural` have no counterpart in locale_string files.
+    pub plural: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PoFile {
+    /// Language from the `Language` header field.
+    pub language: Language,
+    pub entries: Vec<PoEntry>,
+}
+
+impl PoFile {
+    /// Translated entries as sections, labelled by `msgctxt` or by `msgid` when there is no
+    /// context. Fuzzy, untranslated and plural entries are skipped.
+    pub fn sections(&self) -> Vec<Section<'_>> {
+        self.entries
+            .iter()
+            .filter(|e| !e.fuzzy && !e.plural && !e.msgstr.is_empty())
+            .map(|e| {
+                borrowed_section(
+                    e.context.as_deref().unwrap_or(&e.msgid),
+                    [(self.language, e.msgstr.as_str())],
+                )
+            })
+            .collect()
+    }
+}
+
+/// Unescapes a quoted PO string, `None` when it is not a valid one.
+fn unquote(text: &str) -> Option<String> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(match chars.next()? {
+                '\\' => '\\',
+                '"' => '"',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                _ => return None,
+            }),
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+
+    Some(unescaped)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Context,
+    Id,
+    Str,
+    /// `msgid_plural` and `msgstr[N]`, read but not kept.
+    Ignored,
+}
+
+/// Parses a PO file. Obsolete `#~` entries are dropped, the language is read from the header.
+pub fn parse_po(data: &str) -> Result<PoFile, PoError> {
+    let mut entries = vec![];
+    let mut entry = PoEntry::default();
+    let mut field: Option<Field> = None;
+    let mut translated = false;
+
+    for (i, raw_line) in data.lines().enumerate() {
+        let line = raw_line.trim();
+        let syntax_error =
+            || PoError::Syntax(line.to_string(), Location::in_line(i, raw_line, line));
+
+        if line.is_empty() || line.starts_with("#~") {
+            continue;
+        }
+
+        let (keyword, text) = match line.split_once(|c: char| c.is_whitespace()) {
+            _ if line.starts_with('"') => ("", line),
+            Some((keyword, text)) => (keyword, text.trim()),
+            None => (line, ""),
+        };
+
+        // a comment, context or id after a translation starts the next entry
+        let continues_entry =
+            keyword.is_empty() || keyword.starts_with("msgstr") || keyword == "msgid_plural";
+        if translated && !continues_entry {
+            entries.push(std::mem::take(&mut entry));
+            translated = false;
+        }
+
+        match keyword {
+            "" => {}
+            k if k.starts_with('#') => {
+                if let Some(flags) = line.strip_prefix("#,") {
+                    entry.fuzzy |= flags.split(',').any(|f| f.trim() == "fuzzy");
+                }
+                field = None;
+                continue;
+            }
+            "msgctxt" => field = Some(Field::Context),
+            "msgid" => field = Some(Field::Id),
+            "msgid_plural" => {
+                entry.plural = true;
+                field = Some(Field::Ignored);
+            }
+            "msgstr" => field = Some(Field::Str),
+            k if k.starts_with("msgstr[") => field = Some(Field::Ignored),
+            _ => return Err(syntax_error()),
+        }
+        if keyword.starts_with("msgstr") {
+            translated = true;
+        }
+
+        let text = unquote(text).ok_or_else(syntax_error)?;
+        match field {
+            Some(Field::Context) => entry
+                .context
+                .get_or_insert_with(String::new)
+                .push_str(&text),
+            Some(Field::Id) => entry.msgid.push_str(&text),
+            Some(Field::Str) => entry.msgstr.push_str(&text),
+            Some(Field::Ignored) => {}
+            None => return Err(syntax_error()),
+        }
+    }
+    if translated {
+        entries.push(entry);
+    }
+
+    let header = entries
+        .iter()
+        .position(|e| e.context.is_none() && e.msgid.is_empty())
+        .map(|i| entries.remove(i));
+    let tag = header
+        .as_ref()
+        .and_then(|h| h.msgstr.lines().find_map(|l| l.strip_prefix("Language:")))
+        .unwrap_or("")
+        .trim();
+    let language = Language::from_tag(tag).ok_or_else(|| {
+        let location = Location {
+            line: 1,
+            column: 1,
+            length: 1,
+            ..Location::default()
+        };
+        PoError::Language(tag.to_string(), location)
+    })?;
+
+    Ok(PoFile { language, entries })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse_data;
+    use crate::po::{generate_po, parse_po, PoError};
+    use crate::section::Language;
+    use crate::syntax::Document;
+
+    static FILE_STR: &str = "# weapon name\n\
+        section\n\
+        \tTXT\t\"sword\"\n\
+        \tPL\t\"Miecz \\\"Kieł\\\" ostry\"\n\
+        \tEN\t\"Sword\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"gold\"\n\
+        \tPL\t\"Masz %d złota\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"en_only\"\n\
+        \tEN\t\"100%% done\"\n\
+        end\n";
+
+    #[test]
+    fn generates_template() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let pot = generate_po(&sections, &Document::parse(FILE_STR), Language::PL, None);
+
+        assert!(pot.starts_with("msgid \"\"\nmsgstr \"\"\n\"Language: \\n\"\n"));
+        assert!(pot.ends_with(
+            "\n# weapon name\n\
+            msgctxt \"sword\"\n\
+            msgid \"Miecz \\\\\\\"Kieł\\\\\\\" ostry\"\n\
+            msgstr \"\"\n\
+            \n\
+            #, c-format\n\
+            msgctxt \"gold\"\n\
+            msgid \"Masz %d złota\"\n\
+            msgstr \"\"\n\
+            \n\
+            msgctxt \"en_only\"\n\
+            msgid \"en_only\"\n\
+            msgstr \"\"\n"
+        ));
+    }
+
+    #[test]
+    fn uses_label_for_empty_source_text() {
+        let data = "section\n\tTXT\t\"empty\"\n\tPL\t\"\"\n\tEN\t\"Empty\"\nend\n";
+        let sections = parse_data(data).unwrap();
+        let po = generate_po(
+            &sections,
+            &Document::parse(data),
+            Language::PL,
+            Some(Language::EN),
+        );
+
+        assert!(po.ends_with("msgctxt \"empty\"\nmsgid \"empty\"\nmsgstr \"Empty\"\n"));
+        assert_eq!(parse_po(&po).unwrap().entries.len(), 1);
+    }
+
+    #[test]
+    fn generates_translations() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let po = generate_po(
+            &sections,
+            &Document::parse(FILE_STR),
+            Language::PL,
+            Some(Language::EN),
+        );
+
+        assert!(po.contains("\"Language: en\\n\"\n"));
+        assert!(po.contains(
+            "msgctxt \"sword\"\nmsgid \"Miecz \\\\\\\"Kieł\\\\\\\" ostry\"\nmsgstr \"Sword\"\n"
+        ));
+        assert!(po.contains("msgid \"en_only\"\nmsgstr \"100%% done\"\n"));
+        assert!(!po.contains("#, c-format\nmsgctxt \"en_only\""));
+    }
+
+    #[test]
+    fn parses_entries_with_flags_and_escapes() {
+        let po = parse_po(
+            "# header comment\n\
+            msgid \"\"\n\
+            msgstr \"\"\n\
+            \"Content-Type: text/plain; charset=UTF-8\\n\"\n\
+            \"Language: pt_BR\\n\"\n\
+            \n\
+            # translator note\n\
+            #, c-format\n\
+            msgctxt \"gold\"\n\
+            msgid \"Masz %d złota\"\n\
+            msgstr \"Tens %d \"\n\
+            \"\\\"ouro\\\"\\t\\\\\"\n\
+            \n\
+            #, fuzzy, c-format\n\
+            msgctxt \"sword\"\n\
+            msgid \"Miecz\"\n\
+            msgstr \"Espada\"\n\
+            \n\
+            msgid \"no_context\"\n\
+            msgstr \"\"\n\
+            msgid \"%d apple\"\n\
+            msgid_plural \"%d apples\"\n\
+            msgstr[0] \"%d maçã\"\n\
+            msgstr[1] \"%d maçãs\"\n\
+            #~ msgid \"old\"\n\
+            #~ msgstr \"velho\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(po.language, Language::PT);
+        assert_eq!(po.entries.len(), 4);
+        assert_eq!(po.entries[0].context.as_deref(), Some("gold"));
+        assert_eq!(po.entries[0].msgstr, "Tens %d \"ouro\"\t\\");
+        assert!(po.entries[1].fuzzy);
+        assert!(po.entries[3].plural);
+
+        let sections = po.sections();
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].label, "gold");
+        assert_eq!(
+            sections[0].translations[&Language::PT],
+            "Tens %d \"ouro\"\t\\"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let header = "msgid \"\"\nmsgstr \"Language: en\\n\"\n\n";
+
+        assert!(matches!(
+            parse_po(&format!("{}msgid \"a\"\nmsgstr \"bad \\q\"\n", header)),
+            Err(PoError::Syntax(line, l)) if line == "msgstr \"bad \\q\"" && l.line == 5
+        ));
+        assert!(matches!(
+            parse_po(&format!("{}msgid \"a\"\nmsgtxt \"b\"\n", header)),
+            Err(PoError::Syntax(..))
+        ));
+        assert!(matches!(
+            parse_po("msgid \"a\"\nmsgstr \"b\"\n"),
+            Err(PoError::Language(l, _)) if l.is_empty()
+        ));
+    }
+
+    #[test]
+    fn round_trips_exported_translations() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let po = generate_po(
+            &sections,
+            &Document::parse(FILE_STR),
+            Language::PL,
+            Some(Language::EN),
+        );
+        let imported = parse_po(&po).unwrap();
+
+        let expected: Vec<_> = sections
+            .iter()
+            .filter_map(|s| Some((s.label, *s.translations.get(&Language::EN)?)))
+            .collect();
+        let actual: Vec<_> = imported
+            .sections()
+            .iter()
+            .map(|s| (s.label, s.translations[&Language::EN]))
+            .collect();
+        assert_eq!(actual, expected);
+    }
+}
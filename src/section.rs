@@ -1,27 +1,120 @@
-use crate::section::Language::{EN, PL};
-use std::collections::BTreeMap;
-use std::fmt::{Display, Formatter};
+use crate::diagnostic::Diagnostic;
+use crate::encoding::{unrepresentable_chars, Codepages};
+use crate::markup::parse_markup;
+use crate::placeholder::{argument_types, format_argument_types};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+use std::sync::Mutex;
 
-static ARGUMENT_TYPES: [&str; 4] = ["%d", "%s", "%ld", "%%"];
+/// Languages shipped with the server files, in the order they are written out.
+static DEFAULT_LANGUAGES: [&str; 12] = [
+    "PL", "EN", "DE", "TR", "RO", "IT", "ES", "CZ", "HU", "FR", "PT", "GR",
+];
 
-#[derive(Debug, PartialEq)]
+/// Languages which were not known upfront, registered on first use.
+static CUSTOM_LANGUAGES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Section<'a> {
     pub label: &'a str,
     pub translations: BTreeMap<Language, &'a str>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Ord, PartialOrd, Eq)]
-pub enum Language {
-    PL,
-    EN,
+/// Language code interned at runtime. Languages are ordered by registration,
+/// so the default languages always come first and keep their order.
+#[derive(Clone, Copy, PartialEq, Ord, PartialOrd, Eq, Hash)]
+pub struct Language(usize);
+
+#[allow(dead_code)]
+impl Language {
+    pub const PL: Language = Language(0);
+    pub const EN: Language = Language(1);
+    pub const DE: Language = Language(2);
+    pub const TR: Language = Language(3);
+    pub const RO: Language = Language(4);
+    pub const IT: Language = Language(5);
+    pub const ES: Language = Language(6);
+    pub const CZ: Language = Language(7);
+    pub const HU: Language = Language(8);
+    pub const FR: Language = Language(9);
+    pub const PT: Language = Language(10);
+    pub const GR: Language = Language(11);
 }
 
 impl Language {
-    pub fn variants_count() -> usize {
-        2 // PL,EN
+    pub fn code(self) -> &'static str {
+        match DEFAULT_LANGUAGES.get(self.0) {
+            Some(code) => code,
+            None => CUSTOM_LANGUAGES.lock().unwrap()[self.0 - DEFAULT_LANGUAGES.len()],
+        }
+    }
+
+    /// Whether the keyword can name a language, e.g. `PL` or `EN`.
+    pub fn is_code<T>(keyword: T) -> bool
+    where
+        T: AsRef<str>,
+    {
+        let keyword = keyword.as_ref();
+        !keyword.is_empty() && keyword.len() <= 8 && keyword.chars().all(|c| c.is_ascii_uppercase())
+    }
+
+    /// Whether the code is a default language or was registered before, without registering it.
+    pub fn is_registered<T>(code: T) -> bool
+    where
+        T: AsRef<str>,
+    {
+        let code = code.as_ref().trim().to_ascii_uppercase();
+
+        DEFAULT_LANGUAGES.contains(&code.as_str())
+            || CUSTOM_LANGUAGES.lock().unwrap().contains(&code.as_str())
+    }
+
+    /// Language of a tag such as `en`, `pt-BR` or `pt_BR`, only the primary subtag is kept.
+    pub fn from_tag<T>(tag: T) -> Option<Language>
+    where
+        T: AsRef<str>,
+    {
+        let code = tag
+            .as_ref()
+            .trim()
+            .split(['_', '-', '@'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_uppercase();
+
+        match Language::is_code(&code) {
+            true => Some(Language::from(code.as_str())),
+            false => None,
+        }
+    }
+
+    /// Parses a comma separated list of language codes, e.g. `PL,EN,DE`.
+    pub fn parse_list<T>(list: T) -> Vec<Language>
+    where
+        T: AsRef<str>,
+    {
+        list.as_ref()
+            .split(',')
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(Language::from)
+            .collect()
     }
 }
 
+/// Languages used by any of the sections.
+pub fn discover_languages(sections: &[Section]) -> Vec<Language> {
+    sections
+        .iter()
+        .flat_map(|s| s.translations.keys().copied())
+        .collect::<BTreeSet<Language>>()
+        .into_iter()
+        .collect()
+}
+
 impl Section<'_> {
     pub fn new() -> Self {
         Section {
@@ -32,7 +125,7 @@ impl Section<'_> {
     pub fn generate(&self) -> String {
         let mut args: String = "".to_string();
         for x in &self.translations {
-            args.push_str(&*format!("\t{lang}\t\"{text}\"\n", lang = x.0, text = x.1));
+            args.push_str(&format!("\t{lang}\t\"{text}\"\n", lang = x.0, text = x.1));
         }
 
         format!(
@@ -46,73 +139,239 @@ impl Section<'_> {
     }
 
     pub fn check_translations_diversity(&self) -> bool {
-        for x in &self.translations {
-            for y in &self.translations {
-                if x.0 == y.0 {
-                    continue;
-                }
+        self.identical_translations().is_empty()
+    }
 
-                if x.1 == y.1 {
-                    return false;
-                }
+    /// Warnings for translations identical to the translation of an earlier language.
+    pub fn identical_translations(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        for (i, (lang, text)) in self.translations.iter().enumerate() {
+            if let Some((original, _)) = self.translations.iter().take(i).find(|(_, t)| *t == text)
+            {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        "identical-translation",
+                        format!(
+                            "translation {} in label {} is identical to {}",
+                            lang, self.label, original
+                        ),
+                    )
+                    .with_label(self.label)
+                    .with_language(*lang),
+                );
             }
         }
 
-        true
+        diagnostics
     }
 
-    pub fn check_translations_arguments(&self) -> bool {
-        for x in ARGUMENT_TYPES.iter() {
-            let counts = &self
-                .translations
-                .iter()
-                .map(|(_, tr)| tr.match_indices(x).count())
-                .collect::<Vec<usize>>();
-
-            if !counts.is_empty() {
-                if !counts.iter().all(|f| counts.first() == Some(f)) {
-                    return false;
+    /// Errors for translations with a tab or line break, which a locale_string line cannot hold.
+    pub fn unsupported_characters(&self) -> Vec<Diagnostic> {
+        self.translations
+            .iter()
+            .filter(|(_, text)| text.contains(['\t', '\n', '\r']))
+            .map(|(lang, _)| {
+                Diagnostic::error(
+                    "unsupported-character",
+                    format!(
+                        "translation {} in label {} contains a tab or line break",
+                        lang, self.label
+                    ),
+                )
+                .with_label(self.label)
+                .with_language(*lang)
+            })
+            .collect()
+    }
+
+    /// Errors for translations with characters the codepage of their language cannot represent.
+    pub fn check_translations_codepage(&self, codepages: &Codepages) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        for (lang, text) in &self.translations {
+            let codepage = codepages.get(*lang);
+            let chars = unrepresentable_chars(text, codepage);
+            if chars.is_empty() {
+                continue;
+            }
+
+            diagnostics.push(
+                Diagnostic::error(
+                    "unrepresentable-character",
+                    format!(
+                        "translation {} in label {} has characters not representable in {}: {}",
+                        lang,
+                        self.label,
+                        codepage.name(),
+                        chars.into_iter().collect::<String>()
+                    ),
+                )
+                .with_label(self.label)
+                .with_language(*lang),
+            );
+        }
+
+        diagnostics
+    }
+
+    /// Errors for malformed markup. Colors and hyperlinks are compared against the `reference`
+    /// language when the section has it.
+    pub fn check_translations_markup(&self, reference: Language) -> Vec<Diagnostic> {
+        let mut problems = vec![];
+        let mut parsed = vec![];
+        for (lang, text) in &self.translations {
+            match parse_markup(text) {
+                Ok(markup) => parsed.push((*lang, markup)),
+                Err(e) => problems.push((*lang, e)),
+            }
+        }
+
+        if let Some((_, reference_markup)) = parsed.iter().find(|(l, _)| *l == reference) {
+            for (lang, markup) in parsed.iter().filter(|(l, _)| *l != reference) {
+                if let Err(e) = markup.compare(reference_markup) {
+                    problems.push((*lang, e));
                 }
             }
         }
 
-        true
+        problems.sort_by_key(|(lang, _)| *lang);
+        problems
+            .into_iter()
+            .map(|(lang, e)| {
+                Diagnostic::error(
+                    "invalid-markup",
+                    format!(
+                        "invalid markup in translation {} of label {}: {}",
+                        lang, self.label, e
+                    ),
+                )
+                .with_label(self.label)
+                .with_language(lang)
+            })
+            .collect()
+    }
+
+    /// Whether all translations consume the same printf arguments in the same order.
+    pub fn check_translations_arguments(&self) -> bool {
+        self.argument_mismatches().is_empty()
+    }
+
+    /// Errors for translations whose printf arguments differ from the first translation.
+    pub fn argument_mismatches(&self) -> Vec<Diagnostic> {
+        let mut translations = self.translations.iter();
+        let (first_lang, first_types) = match translations.next() {
+            Some((lang, text)) => (lang, argument_types(text)),
+            None => return vec![],
+        };
+
+        translations
+            .map(|(lang, text)| (lang, argument_types(text)))
+            .filter(|(_, types)| *types != first_types)
+            .map(|(lang, types)| {
+                Diagnostic::error(
+                    "argument-mismatch",
+                    format!(
+                        "translation {} in label {} takes arguments {} but {} takes {}",
+                        lang,
+                        self.label,
+                        format_argument_types(&types),
+                        first_lang,
+                        format_argument_types(&first_types)
+                    ),
+                )
+                .with_label(self.label)
+                .with_language(*lang)
+            })
+            .collect()
     }
 }
 
-pub fn check_string_arguments<T>(left: T, right: T) -> bool
+/// Section borrowing its label and translations from strings owned elsewhere.
+pub fn borrowed_section<'a, I>(label: &'a str, translations: I) -> Section<'a>
 where
-    T: AsRef<str>,
+    I: IntoIterator<Item = (Language, &'a str)>,
 {
-    for x in ARGUMENT_TYPES.iter() {
-        if left.as_ref().match_indices(x).count() != right.as_ref().match_indices(x).count() {
-            return false;
-        }
+    Section {
+        label,
+        translations: translations.into_iter().collect(),
     }
+}
 
-    true
+/// Whether both strings consume the same printf arguments in the same order.
+pub fn check_string_arguments<T>(left: T, right: T) -> bool
+where
+    T: AsRef<str>,
+{
+    argument_types(left.as_ref()) == argument_types(right.as_ref())
 }
 
 impl From<&str> for Language {
     fn from(s: &str) -> Self {
-        match s {
-            "PL" => PL,
-            "EN" => EN,
-            lang => panic!("invalid language {}", lang),
+        let code = s.trim().to_ascii_uppercase();
+        if let Some(i) = DEFAULT_LANGUAGES.iter().position(|l| *l == code) {
+            return Language(i);
+        }
+
+        let mut custom = CUSTOM_LANGUAGES.lock().unwrap();
+        let i = match custom.iter().position(|l| *l == code) {
+            Some(i) => i,
+            None => {
+                custom.push(Box::leak(code.into_boxed_str()));
+                custom.len() - 1
+            }
+        };
+
+        Language(DEFAULT_LANGUAGES.len() + i)
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    /// Parses a language code such as `PL` or `en`, rejecting anything else.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Language::is_code(s.trim().to_ascii_uppercase()) {
+            true => Ok(Language::from(s)),
+            false => Err(format!("invalid language code {}", s)),
         }
     }
 }
 
 impl Display for Language {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self)
+        write!(f, "{}", self.code())
+    }
+}
+
+impl Serialize for Language {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.code())
+    }
+}
+
+impl<'de> Deserialize<'de> for Language {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+impl Debug for Language {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::section::Language::{EN, PL};
-    use crate::section::{Language, Section};
+    use crate::encoding::{parse_codepage, Codepages};
+    use crate::section::{check_string_arguments, discover_languages, Language, Section};
 
     #[test]
     fn from_trait_language_works() {
@@ -122,16 +381,188 @@ mod tests {
         assert_eq!(en, Language::EN);
     }
 
+    #[test]
+    fn from_trait_registers_unknown_language() {
+        let kr: Language = "KR".into();
+        assert_eq!(kr, Language::from("kr"));
+        assert_eq!(kr.to_string(), "KR");
+        assert!(Language::GR < kr);
+    }
+
+    #[test]
+    fn parses_only_valid_language_codes() {
+        assert_eq!("pl".parse::<Language>(), Ok(Language::PL));
+        assert_eq!(
+            "pl-PL".parse::<Language>(),
+            Err("invalid language code pl-PL".to_string())
+        );
+        assert!(!Language::is_registered("PL-PL"));
+    }
+
+    #[test]
+    fn parses_language_list() {
+        assert_eq!(
+            Language::parse_list("pl, EN,,DE"),
+            vec![Language::PL, Language::EN, Language::DE]
+        );
+    }
+
+    #[test]
+    fn parses_language_tags() {
+        assert_eq!(Language::from_tag("pt_BR"), Some(Language::PT));
+        assert_eq!(Language::from_tag(" en-US "), Some(Language::EN));
+        assert_eq!(Language::from_tag("de"), Some(Language::DE));
+        assert_eq!(Language::from_tag(""), None);
+        assert_eq!(Language::from_tag("x1"), None);
+    }
+
+    #[test]
+    fn discovers_languages_from_sections() {
+        let mut s1 = Section::new();
+        s1.translations.insert(Language::TR, "tr");
+        let mut s2 = Section::new();
+        s2.translations.insert(Language::EN, "en");
+        s2.translations.insert(Language::PL, "pl");
+
+        assert_eq!(
+            discover_languages(&[s1, s2]),
+            vec![Language::PL, Language::EN, Language::TR]
+        );
+    }
+
     #[test]
     fn generates_file_data() {
         let mut section = Section::new();
         section.label = "lab1";
-        section.translations.insert(PL, "tr1");
-        section.translations.insert(EN, "tr2");
+        section.translations.insert(Language::PL, "tr1");
+        section.translations.insert(Language::EN, "tr2");
 
         assert_eq!(
             section.generate(),
             "section\n\tTXT\t\"lab1\"\n\tPL\t\"tr1\"\n\tEN\t\"tr2\"\nend"
         )
     }
+
+    #[test]
+    fn finds_characters_outside_codepage() {
+        let mut section = Section::new();
+        section.label = "greeting";
+        section.translations.insert(Language::PL, "Cześć");
+        section.translations.insert(Language::DE, "Grüße, ğ");
+        section.translations.insert(Language::TR, "Merhaba ğ");
+
+        let diagnostics = section.check_translations_codepage(&Codepages::default());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].language, Some(Language::DE));
+        assert_eq!(
+            diagnostics[0].message,
+            "translation DE in label greeting has characters not representable in windows-1252: ğ"
+        );
+
+        let codepages: Codepages = vec![parse_codepage("TR=cp1252").unwrap()]
+            .into_iter()
+            .collect();
+        let languages: Vec<_> = section
+            .check_translations_codepage(&codepages)
+            .iter()
+            .map(|d| d.language)
+            .collect();
+        assert_eq!(languages, vec![Some(Language::DE), Some(Language::TR)]);
+    }
+
+    #[test]
+    fn compares_argument_order() {
+        let mut section = Section::new();
+        section.label = "kill";
+        section
+            .translations
+            .insert(Language::PL, "%s zabił %d potworów (100%%)");
+        section
+            .translations
+            .insert(Language::EN, "%s killed %5d monsters");
+        assert!(section.check_translations_arguments());
+
+        section
+            .translations
+            .insert(Language::DE, "%d Monster von %s getötet");
+        assert!(!section.check_translations_arguments());
+        let mismatches = section.argument_mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].language, Some(Language::DE));
+        assert_eq!(
+            mismatches[0].message,
+            "translation DE in label kill takes arguments %d, %s but PL takes %s, %d"
+        );
+
+        assert!(check_string_arguments("%%d %s", "%s"));
+        assert!(!check_string_arguments("%u", "%lld"));
+    }
+
+    #[test]
+    fn checks_markup_against_reference() {
+        let mut section = Section::new();
+        section.label = "drop";
+        section
+            .translations
+            .insert(Language::PL, "|cffffd700|Hitem:19|h[Miecz]|h|r wypadł");
+        section
+            .translations
+            .insert(Language::EN, "|cffffd700|Hitem:19|h[Sword]|h|r dropped");
+        section
+            .translations
+            .insert(Language::DE, "|Hitem:19|h[Schwert]|h");
+        section
+            .translations
+            .insert(Language::TR, "|cffffd700Kılıç düştü");
+
+        let problems = section.check_translations_markup(Language::PL);
+
+        assert_eq!(problems.len(), 2);
+        assert_eq!(problems[0].language, Some(Language::DE));
+        assert!(problems[0].message.contains("colors [] differ"));
+        assert_eq!(problems[1].language, Some(Language::TR));
+        assert!(problems[1]
+            .message
+            .ends_with("1 color(s) not closed with |r"));
+    }
+
+    #[test]
+    fn reports_identical_translations() {
+        let mut section = Section::new();
+        section.label = "ok";
+        section.translations.insert(Language::PL, "OK");
+        section.translations.insert(Language::EN, "OK");
+        section.translations.insert(Language::DE, "Gut");
+
+        let diagnostics = section.identical_translations();
+
+        assert!(!section.check_translations_diversity());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "translation EN in label ok is identical to PL"
+        );
+    }
+
+    #[test]
+    fn reports_tabs_and_line_breaks() {
+        let mut section = Section::new();
+        section.label = "note";
+        section.translations.insert(Language::PL, "Dwie\nlinie");
+        section.translations.insert(Language::EN, "Tab\there");
+        section.translations.insert(Language::DE, "Notiz");
+
+        let languages: Vec<_> = section
+            .unsupported_characters()
+            .iter()
+            .map(|d| (d.code, d.language))
+            .collect();
+        assert_eq!(
+            languages,
+            vec![
+                ("unsupported-character", Some(Language::PL)),
+                ("unsupported-character", Some(Language::EN))
+            ]
+        );
+    }
 }
@@ -0,0 +1,426 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Located, Location};
+use crate::parse::ParseError;
+use crate::placeholder::{argument_types, format_argument_types};
+use crate::section::{borrowed_section, check_string_arguments, Language, Section};
+use std::path::Path;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SheetError {
+    #[error("invalid spreadsheet: {0}")]
+    Csv(String, Location),
+    #[error("column {0:?} {1}")]
+    Header(String, &'static str, Location),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl SheetError {
+    /// Sets the name of the file the error comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        match &mut self {
+            SheetError::Csv(_, l) | SheetError::Header(_, _, l) => l.set_file(file),
+            SheetError::Parse(_) | SheetError::Io(_) => {}
+        }
+
+        self
+    }
+}
+
+impl Located for SheetError {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            SheetError::Csv(_, l) | SheetError::Header(_, _, l) => Some(l),
+            SheetError::Parse(e) => e.location(),
+            SheetError::Io(_) => None,
+        }
+    }
+}
+
+impl From<SheetError> for Diagnostic {
+    fn from(error: SheetError) -> Self {
+        let location = error.location().cloned();
+        let code = match error {
+            SheetError::Csv(..) => "sheet-syntax",
+            SheetError::Header(..) => "sheet-header",
+            SheetError::Parse(e) => return e.into(),
+            SheetError::Io(e) => return ParseError::Io(e).into(),
+        };
+
+        Diagnostic::error(code, error.to_string())
+            .with_location(location)
+            .with_kind(DiagnosticKind::Parse)
+    }
+}
+
+impl From<csv::Error> for SheetError {
+    fn from(error: csv::Error) -> Self {
+        let location = Location {
+            line: error.position().map_or(1, |p| p.line() as usize),
+            column: 1,
+            length: 1,
+            ..Location::default()
+        };
+
+        SheetError::Csv(error.to_string(), location)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SheetFormat {
+    Csv,
+    Tsv,
+}
+
+impl SheetFormat {
+    /// Format matching the extension of the file, `.tsv` and `.tab` files are tab separated.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some(e) if e.eq_ignore_ascii_case("tsv") || e.eq_ignore_ascii_case("tab") => {
+                SheetFormat::Tsv
+            }
+            _ => SheetFormat::Csv,
+        }
+    }
+
+    fn delimiter(self) -> u8 {
+        match self {
+            SheetFormat::Csv => b',',
+            SheetFormat::Tsv => b'\t',
+        }
+    }
+}
+
+impl FromStr for SheetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(SheetFormat::Csv),
+            "tsv" => Ok(SheetFormat::Tsv),
+            f => Err(format!("invalid sheet format {}, expected csv or tsv", f)),
+        }
+    }
+}
+
+/// Generates a sheet with a `label` column followed by one column per language. Missing
+/// translations are left empty.
+pub fn generate_sheet(
+    sections: &[Section],
+    languages: &[Language],
+    format: SheetFormat,
+) -> Result<String, SheetError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(format.delimiter())
+        .from_writer(vec![]);
+
+    writer.write_record(std::iter::once("label").chain(languages.iter().map(|l| l.code())))?;
+    for section in sections {
+        writer.write_record(
+            std::iter::once(section.label).chain(
+                languages
+                    .iter()
+                    .map(|l| section.translations.get(l).copied().unwrap_or("")),
+            ),
+        )?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok(String::from_utf8(bytes).expect("sheet is generated from UTF-8 text"))
+}
+
+/// Row of an imported sheet, empty cells are left out.
+#[derive(Debug, PartialEq)]
+pub struct SheetRow {
+    pub label: String,
+    pub translations: Vec<(Language, String)>,
+    pub line: usize,
+}
+
+/// Parses a sheet whose header names the `label` column followed by language columns.
+pub fn parse_sheet(data: &str, format: SheetFormat) -> Result<Vec<SheetRow>, SheetError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(format.delimiter())
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let header_location = Location {
+        line: 1,
+        column: 1,
+        length: 1,
+        ..Location::default()
+    };
+
+    let headers = reader.headers()?.clone();
+    let mut columns = headers.iter().map(|h| h.trim());
+    match columns.next() {
+        Some(h) if h.eq_ignore_ascii_case("label") => {}
+        h => {
+            return Err(SheetError::Header(
+                h.unwrap_or("").to_string(),
+                "must be \"label\", the first column holds the labels",
+                header_location,
+            ))
+        }
+    }
+    let mut languages = vec![];
+    for column in columns {
+        if !Language::is_code(column.to_ascii_uppercase()) {
+            let reason = "is not a language code";
+            return Err(SheetError::Header(
+                column.to_string(),
+                reason,
+                header_location,
+            ));
+        }
+        let language = Language::from(column);
+        if languages.contains(&language) {
+            let reason = "repeats the language of an earlier column";
+            return Err(SheetError::Header(
+                column.to_string(),
+                reason,
+                header_location,
+            ));
+        }
+        languages.push(language);
+    }
+
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let label = record.get(0).unwrap_or("").trim();
+        if label.is_empty() {
+            continue;
+        }
+
+        rows.push(SheetRow {
+            label: label.to_string(),
+            translations: languages
+                .iter()
+                .zip(record.iter().skip(1))
+                .filter(|(_, text)| !text.is_empty())
+                .map(|(lang, text)| (*lang, text.to_string()))
+                .collect(),
+            line: record.position().map_or(0, |p| p.line() as usize),
+        });
+    }
+
+    Ok(rows)
+}
+
+fn row_location(row: &SheetRow) -> Location {
+    Location {
+        line: row.line,
+        column: 1,
+        length: 1,
+        ..Location::default()
+    }
+}
+
+/// Sections of the rows which can be merged into `base`. Rows of labels missing in `base`
+/// are reported as warnings, rows with a translation which cannot be written in a
+/// locale_string line or whose printf arguments differ from the source text of the label are
+/// rejected as errors.
+pub fn sheet_sections<'a>(
+    rows: &'a [SheetRow],
+    base: &[Section],
+) -> (Vec<Section<'a>>, Vec<Diagnostic>) {
+    let mut sections = vec![];
+    let mut problems = vec![];
+
+    for row in rows {
+        let existing = match base.iter().find(|s| s.label == row.label) {
+            Some(s) => s,
+            None => {
+                let location = Location {
+                    line: row.line,
+                    column: 1,
+                    length: row.label.chars().count().max(1),
+                    ..Location::default()
+                };
+                problems.push(
+                    Diagnostic::warning(
+                        "unknown-label",
+                        format!("label {} does not exist, the row is skipped", row.label),
+                    )
+                    .with_label(&row.label)
+                    .with_location(Some(location)),
+                );
+                continue;
+            }
+        };
+        let reference = existing
+            .translations
+            .iter()
+            .next()
+            .map(|(lang, text)| (*lang, *text));
+
+        let section = borrowed_section(
+            &row.label,
+            row.translations
+                .iter()
+                .map(|(lang, text)| (*lang, text.as_str())),
+        );
+
+        let unsupported = section.unsupported_characters();
+        let mut rejected = !unsupported.is_empty();
+        for (lang, text) in &section.translations {
+            if let Some(problem) = unsupported.iter().find(|d| d.language == Some(*lang)) {
+                problems.push(problem.clone().with_location(Some(row_location(row))));
+            } else if let Some((source, source_text)) =
+                reference.filter(|(_, t)| !check_string_arguments(*t, text))
+            {
+                problems.push(
+                    Diagnostic::error(
+                        "argument-mismatch",
+                        format!(
+                            "translation {} in label {} takes arguments {} but {} \"{}\" takes {}",
+                            lang,
+                            row.label,
+                            format_argument_types(&argument_types(text)),
+                            source,
+                            source_text,
+                            format_argument_types(&argument_types(source_text))
+                        ),
+                    )
+                    .with_label(&row.label)
+                    .with_language(*lang)
+                    .with_location(Some(row_location(row))),
+                );
+                rejected = true;
+            }
+        }
+
+        if !rejected {
+            sections.push(section);
+        }
+    }
+
+    (sections, problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse_data;
+    use crate::section::Language;
+    use crate::sheet::{
+        generate_sheet, parse_sheet, sheet_sections, SheetError, SheetFormat, SheetRow,
+    };
+
+    static FILE_STR: &str = "section\n\
+        \tTXT\t\"sword\"\n\
+        \tPL\t\"Miecz, \"ostry\" miecz\"\n\
+        \tEN\t\"Sword\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"gold\"\n\
+        \tPL\t\"Masz %d złota\"\n\
+        end\n";
+
+    #[test]
+    fn generates_quoted_csv_and_tsv() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let languages = [Language::PL, Language::EN];
+
+        assert_eq!(
+            generate_sheet(&sections, &languages, SheetFormat::Csv).unwrap(),
+            "label,PL,EN\n\
+            sword,\"Miecz, \"\"ostry\"\" miecz\",Sword\n\
+            gold,Masz %d złota,\n"
+        );
+        assert_eq!(
+            generate_sheet(&sections, &languages, SheetFormat::Tsv).unwrap(),
+            "label\tPL\tEN\n\
+            sword\t\"Miecz, \"\"ostry\"\" miecz\"\tSword\n\
+            gold\tMasz %d złota\t\n"
+        );
+    }
+
+    #[test]
+    fn parses_quoted_cells() {
+        let rows = parse_sheet(
+            "label\tPL\ten\n\
+            sword\t\"Miecz\tz\nprzerwą\"\tSword\n\
+            \t\t\n\
+            gold\tMasz %d złota\n",
+            SheetFormat::Tsv,
+        )
+        .unwrap();
+
+        assert_eq!(
+            rows,
+            vec![
+                SheetRow {
+                    label: "sword".to_string(),
+                    translations: vec![
+                        (Language::PL, "Miecz\tz\nprzerwą".to_string()),
+                        (Language::EN, "Sword".to_string())
+                    ],
+                    line: 2,
+                },
+                SheetRow {
+                    label: "gold".to_string(),
+                    translations: vec![(Language::PL, "Masz %d złota".to_string())],
+                    line: 5,
+                }
+            ]
+        );
+        assert!(matches!(
+            parse_sheet("label,PL,English text\n", SheetFormat::Csv),
+            Err(SheetError::Header(h, _, _)) if h == "English text"
+        ));
+        assert!(matches!(
+            parse_sheet("PL,EN\n", SheetFormat::Csv),
+            Err(SheetError::Header(h, _, _)) if h == "PL"
+        ));
+        assert!(matches!(
+            parse_sheet("label,PL,pl\n", SheetFormat::Csv),
+            Err(SheetError::Header(h, _, _)) if h == "pl"
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_labels_and_mismatched_arguments() {
+        let base = parse_data(FILE_STR).unwrap();
+        let rows = parse_sheet(
+            "label,PL,EN,DE\n\
+            sword,,Blade,Schwert\n\
+            gold,,You have %s gold,Du hast %d Gold\n\
+            shield,,Shield,\n\
+            sword,,\"Two\nlines\",\n",
+            SheetFormat::Csv,
+        )
+        .unwrap();
+
+        let (sections, problems) = sheet_sections(&rows, &base);
+
+        assert_eq!(sections.len(), 1);
+        assert_eq!(sections[0].label, "sword");
+        assert_eq!(sections[0].translations[&Language::DE], "Schwert");
+
+        let codes: Vec<_> = problems
+            .iter()
+            .map(|d| (d.code, d.label.as_deref()))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                ("argument-mismatch", Some("gold")),
+                ("unknown-label", Some("shield")),
+                ("unsupported-character", Some("sword")),
+            ]
+        );
+        assert_eq!(problems[0].language, Some(Language::EN));
+        assert_eq!(problems[1].location.as_ref().unwrap().line, 4);
+    }
+
+    #[test]
+    fn detects_format_from_extension() {
+        assert_eq!(SheetFormat::from_path("a/b.TSV"), SheetFormat::Tsv);
+        assert_eq!(SheetFormat::from_path("a/b.csv"), SheetFormat::Csv);
+        assert_eq!(SheetFormat::from_path("sheet"), SheetFormat::Csv);
+    }
+}
@@ -0,0 +1,182 @@
+use crate::merge::ReportFormat;
+use crate::section::{Language, Section};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// Translation progress of a single language.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct LanguageStats {
+    pub language: Language,
+    pub translated: usize,
+    pub missing: usize,
+    pub percent: f64,
+    /// Translations identical to the source language, always 0 for the source itself.
+    pub identical_to_source: usize,
+    /// Translations whose printf arguments differ from the source language.
+    pub argument_mismatches: usize,
+}
+
+/// Translation progress of every language of a file.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Stats {
+    pub labels: usize,
+    pub source: Language,
+    pub languages: Vec<LanguageStats>,
+}
+
+/// Section holding only the source and the given translation of a label.
+fn pair<'a>(section: &Section<'a>, source: Language, lang: Language) -> Option<Section<'a>> {
+    let mut translations = BTreeMap::new();
+    translations.insert(source, *section.translations.get(&source)?);
+    translations.insert(lang, *section.translations.get(&lang)?);
+
+    Some(Section {
+        label: section.label,
+        translations,
+    })
+}
+
+impl Stats {
+    /// Counts translations of every language. The first language is the source the others
+    /// are compared against.
+    pub fn new(sections: &[Section], languages: &[Language]) -> Self {
+        let source = languages.first().copied().unwrap_or(Language::PL);
+        let mut stats = vec![];
+
+        for lang in languages {
+            let translated = sections
+                .iter()
+                .filter(|s| s.translations.contains_key(lang))
+                .count();
+            let pairs: Vec<Section> = sections
+                .iter()
+                .filter(|_| *lang != source)
+                .filter_map(|s| pair(s, source, *lang))
+                .collect();
+
+            stats.push(LanguageStats {
+                language: *lang,
+                translated,
+                missing: sections.len() - translated,
+                percent: match sections.len() {
+                    0 => 100.0,
+                    n => (translated as f64 * 1000.0 / n as f64).round() / 10.0,
+                },
+                identical_to_source: pairs
+                    .iter()
+                    .filter(|s| !s.check_translations_diversity())
+                    .count(),
+                argument_mismatches: pairs
+                    .iter()
+                    .filter(|s| !s.check_translations_arguments())
+                    .count(),
+            });
+        }
+
+        Stats {
+            labels: sections.len(),
+            source,
+            languages: stats,
+        }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> serde_json::Result<String> {
+        match format {
+            ReportFormat::Text => Ok(self.to_string()),
+            ReportFormat::Json => serde_json::to_string_pretty(self),
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Labels: {}, source language: {}",
+            self.labels, self.source
+        )?;
+        writeln!(
+            f,
+            "{:<8} {:>10} {:>8} {:>9} {:>10} {:>10}",
+            "Language", "Translated", "Missing", "Complete", "Identical", "Arguments"
+        )?;
+
+        for l in &self.languages {
+            writeln!(
+                f,
+                "{:<8} {:>10} {:>8} {:>8.1}% {:>10} {:>10}",
+                l.language.code(),
+                l.translated,
+                l.missing,
+                l.percent,
+                l.identical_to_source,
+                l.argument_mismatches
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::ReportFormat;
+    use crate::section::Language;
+    use crate::stats::Stats;
+    use crate::test_support::section;
+
+    #[test]
+    fn counts_translations_per_language() {
+        let sections = vec![
+            section("a", &[(Language::PL, "Miecz"), (Language::EN, "Sword")]),
+            section("b", &[(Language::PL, "OK"), (Language::EN, "OK")]),
+            section("c", &[(Language::PL, "%d zł"), (Language::EN, "%s gold")]),
+            section("d", &[(Language::PL, "Tarcza")]),
+        ];
+
+        let stats = Stats::new(&sections, &[Language::PL, Language::EN, Language::DE]);
+
+        assert_eq!(stats.labels, 4);
+        let en = &stats.languages[1];
+        assert_eq!((en.translated, en.missing, en.percent), (3, 1, 75.0));
+        assert_eq!((en.identical_to_source, en.argument_mismatches), (1, 1));
+        let de = &stats.languages[2];
+        assert_eq!((de.translated, de.missing, de.percent), (0, 4, 0.0));
+    }
+
+    #[test]
+    fn renders_table_and_json() {
+        let sections = vec![
+            section("a", &[(Language::PL, "Miecz"), (Language::EN, "Sword")]),
+            section("b", &[(Language::PL, "Tarcza")]),
+            section("c", &[(Language::PL, "Łuk")]),
+        ];
+        let stats = Stats::new(&sections, &[Language::PL, Language::EN]);
+
+        assert_eq!(
+            stats.render(ReportFormat::Text).unwrap(),
+            "Labels: 3, source language: PL\n\
+            Language Translated  Missing  Complete  Identical  Arguments\n\
+            PL                3        0    100.0%          0          0\n\
+            EN                1        2     33.3%          0          0\n"
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&stats.render(ReportFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["languages"][1]["language"], "EN");
+        assert_eq!(json["languages"][1]["percent"], 33.3);
+    }
+
+    #[test]
+    fn rounds_percentages() {
+        let sections = vec![
+            section("a", &[(Language::PL, "Miecz"), (Language::EN, "Sword")]),
+            section("b", &[(Language::PL, "Tarcza"), (Language::EN, "Shield")]),
+            section("c", &[(Language::PL, "Łuk")]),
+        ];
+        let stats = Stats::new(&sections, &[Language::PL, Language::EN]);
+
+        assert_eq!(stats.languages[1].percent, 66.7);
+    }
+}
@@ -0,0 +1,432 @@
+use crate::parse::{extract_text, line_keyword};
+use crate::section::{Language, Section};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// Single line of a file, kept exactly as it was read.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line<'a> {
+    pub text: Cow<'a, str>,
+    /// `\n`, `\r\n` or empty for the last line without a line break.
+    pub ending: &'a str,
+}
+
+impl Line<'_> {
+    fn keyword(&self) -> &str {
+        line_keyword(self.text.trim())
+    }
+
+    fn is_comment(&self) -> bool {
+        self.text.trim().starts_with('#')
+    }
+}
+
+/// Section together with the comments written directly above it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SectionNode<'a> {
+    pub comments: Vec<Line<'a>>,
+    /// Lines from `section` to `end`, including comments inside the section.
+    pub lines: Vec<Line<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Node<'a> {
+    /// Blank lines and comments which do not belong to any section.
+    Trivia(Vec<Line<'a>>),
+    Section(SectionNode<'a>),
+}
+
+/// Lossless syntax tree of a locale_string file. Generating an unchanged document gives back
+/// the exact same bytes it was parsed from.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document<'a> {
+    pub nodes: Vec<Node<'a>>,
+}
+
+fn split_lines(data: &str) -> Vec<Line<'_>> {
+    data.split_inclusive('\n')
+        .map(|l| {
+            let text = l.trim_end_matches('\n').trim_end_matches('\r');
+            Line {
+                text: Cow::Borrowed(text),
+                ending: &l[text.len()..],
+            }
+        })
+        .collect()
+}
+
+impl<'a> SectionNode<'a> {
+    fn new(comments: Vec<Line<'a>>, first: Line<'a>) -> Self {
+        SectionNode {
+            comments,
+            lines: vec![first],
+        }
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.lines
+            .iter()
+            .find(|l| l.keyword() == "TXT")
+            .and_then(|l| extract_text(l.text.trim(), "TXT"))
+    }
+
+    /// Text of the comments above and inside the section, without the leading `#`.
+    pub fn comments(&self) -> Vec<&str> {
+        self.comments
+            .iter()
+            .chain(self.lines.iter())
+            .filter(|l| l.is_comment())
+            .map(|l| l.text.trim().trim_start_matches('#').trim())
+            .collect()
+    }
+
+    fn translation_line(&self, lang: Language) -> Option<usize> {
+        self.lines.iter().position(|l| l.keyword() == lang.code())
+    }
+
+    /// Rewrites only the lines whose translation differs from the section.
+    fn update(&mut self, section: &Section, ending: &'a str) {
+        for (lang, text) in &section.translations {
+            match self.translation_line(*lang) {
+                Some(i) => {
+                    let line = &self.lines[i];
+                    let trimmed = line.text.trim();
+                    if extract_text(trimmed, lang.code()) == Some(*text) {
+                        continue;
+                    }
+
+                    // keep indentation and separator, replace only the quoted text
+                    let keyword_start = line.text.find(trimmed).unwrap_or(0);
+                    let prefix = match trimmed.find('\t') {
+                        Some(tab) => line.text[..keyword_start + tab + 1].to_string(),
+                        None => format!("{}\t", line.text),
+                    };
+                    self.lines[i].text = Cow::Owned(format!("{}\"{}\"", prefix, text));
+                }
+                None => {
+                    let indent = self
+                        .lines
+                        .iter()
+                        .find(|l| l.keyword() == "TXT")
+                        .map(|l| {
+                            let t = l.text.trim_start();
+                            l.text[..l.text.len() - t.len()].to_string()
+                        })
+                        .unwrap_or_else(|| "\t".to_string());
+                    let end = self
+                        .lines
+                        .iter()
+                        .position(|l| l.keyword() == "end")
+                        .unwrap_or(self.lines.len());
+
+                    self.lines.insert(
+                        end,
+                        Line {
+                            text: Cow::Owned(format!("{}{}\t\"{}\"", indent, lang, text)),
+                            ending,
+                        },
+                    );
+                }
+            }
+        }
+
+        self.lines.retain(|l| {
+            let keyword = l.keyword();
+            keyword == "TXT"
+                || !Language::is_code(keyword)
+                || section.translations.contains_key(&Language::from(keyword))
+        });
+    }
+}
+
+impl<'a> Document<'a> {
+    pub fn parse(data: &'a str) -> Self {
+        let mut nodes = vec![];
+        let mut trivia: Vec<Line> = vec![];
+        let mut current: Option<SectionNode> = None;
+
+        for line in split_lines(data) {
+            let keyword = line.keyword().to_string();
+
+            if keyword == "section" {
+                if let Some(section) = current.take() {
+                    nodes.push(Node::Section(section));
+                }
+
+                // comments directly above the section belong to it
+                let attached = trivia.iter().rev().take_while(|l| l.is_comment()).count();
+                let comments = trivia.split_off(trivia.len() - attached);
+                if !trivia.is_empty() {
+                    nodes.push(Node::Trivia(std::mem::take(&mut trivia)));
+                }
+
+                current = Some(SectionNode::new(comments, line));
+                continue;
+            }
+
+            match current.as_mut() {
+                Some(section) => {
+                    section.lines.push(line);
+                    if keyword == "end" {
+                        nodes.push(Node::Section(current.take().unwrap()));
+                    }
+                }
+                None => trivia.push(line),
+            }
+        }
+
+        if let Some(section) = current {
+            nodes.push(Node::Section(section));
+        }
+        if !trivia.is_empty() {
+            nodes.push(Node::Trivia(trivia));
+        }
+
+        Document { nodes }
+    }
+
+    fn lines(&self) -> impl Iterator<Item = &Line<'a>> {
+        self.nodes
+            .iter()
+            .flat_map(|n| -> Box<dyn Iterator<Item = &Line<'a>> + '_> {
+                match n {
+                    Node::Trivia(lines) => Box::new(lines.iter()),
+                    Node::Section(s) => Box::new(s.comments.iter().chain(s.lines.iter())),
+                }
+            })
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &SectionNode<'a>> {
+        self.nodes.iter().filter_map(|n| match n {
+            Node::Section(s) => Some(s),
+            Node::Trivia(_) => None,
+        })
+    }
+
+    fn line_ending(&self) -> &'static str {
+        match self.lines().next() {
+            Some(l) if l.ending == "\r\n" => "\r\n",
+            _ => "\n",
+        }
+    }
+
+    pub fn generate(&self) -> String {
+        self.lines()
+            .map(|l| format!("{}{}", l.text, l.ending))
+            .collect()
+    }
+
+    /// Makes the document contain exactly the given sections, touching only the lines which
+    /// changed. Sections missing in the document are appended at its end.
+    pub fn apply(&mut self, sections: &[Section]) {
+        let ending = self.line_ending();
+        let labels: HashSet<&str> = sections.iter().map(|s| s.label).collect();
+
+        self.nodes.retain(|n| match n {
+            Node::Section(node) => match node.label() {
+                Some(label) => labels.contains(label),
+                None => true,
+            },
+            Node::Trivia(_) => true,
+        });
+
+        // the first node wins for labels the document holds twice
+        let mut positions: HashMap<&str, usize> = HashMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if let Node::Section(node) = node {
+                if let Some(label) = node.label().and_then(|l| labels.get(l)) {
+                    positions.entry(*label).or_insert(i);
+                }
+            }
+        }
+
+        for s in sections {
+            match positions.get(s.label) {
+                Some(&i) => {
+                    if let Node::Section(node) = &mut self.nodes[i] {
+                        node.update(s, ending);
+                    }
+                }
+                None => {
+                    self.append(s, ending);
+                    positions.insert(s.label, self.nodes.len() - 1);
+                }
+            }
+        }
+    }
+
+    /// Puts the comment of the label directly above its section, replacing the comment starting
+    /// with `prefix` left by an earlier run. Other comments are kept.
+    pub fn set_comments(&mut self, prefix: &str, comments: &HashMap<&str, String>) {
+        let ending = self.line_ending();
+
+        for node in self.nodes.iter_mut() {
+            if let Node::Section(node) = node {
+                let comment = node.label().and_then(|l| comments.get(l)).cloned();
+                node.comments.retain(|l| !l.text.trim().starts_with(prefix));
+                if let Some(comment) = comment {
+                    node.comments.push(Line {
+                        text: Cow::Owned(comment),
+                        ending,
+                    });
+                }
+            }
+        }
+    }
+
+    fn append(&mut self, section: &Section, ending: &'a str) {
+        let mut separator = vec![];
+        match self.lines().last() {
+            None => {}
+            Some(last) if last.ending.is_empty() => {
+                let last = self.last_line_mut().unwrap();
+                last.ending = ending;
+                separator.push(Line {
+                    text: Cow::Borrowed(""),
+                    ending,
+                });
+            }
+            Some(last) if !last.text.trim().is_empty() => separator.push(Line {
+                text: Cow::Borrowed(""),
+                ending,
+            }),
+            Some(_) => {}
+        }
+        if !separator.is_empty() {
+            self.nodes.push(Node::Trivia(separator));
+        }
+
+        let generated = section.generate();
+        let mut lines = generated.lines().map(|l| Line {
+            text: Cow::Owned(l.to_string()),
+            ending,
+        });
+        let mut node = SectionNode::new(vec![], lines.next().unwrap());
+        node.lines.extend(lines);
+        self.nodes.push(Node::Section(node));
+    }
+
+    fn last_line_mut(&mut self) -> Option<&mut Line<'a>> {
+        match self.nodes.last_mut()? {
+            Node::Trivia(lines) => lines.last_mut(),
+            Node::Section(s) => s.lines.last_mut(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse_data;
+    use crate::section::{Language, Section};
+    use crate::syntax::{Document, Node};
+    use std::collections::HashMap;
+
+    static FILE_STR: &str = "# header\r\n\
+        \r\n\
+        # first section\r\n\
+        section\r\n\
+        \x20   TXT  \t\"s1\"\r\n\
+        \x20   # inner\r\n\
+        \x20   PL\t\"pl1\"\r\n\
+        end\r\n\
+        \r\n\
+        section\r\n\
+        \tTXT\t\"s2\"\r\n\
+        \tPL\t\"pl2\"\r\n\
+        \tEN\t\"en2\"\r\n\
+        end";
+
+    #[test]
+    fn generates_identical_bytes() {
+        assert_eq!(Document::parse(FILE_STR).generate(), FILE_STR);
+        assert_eq!(Document::parse("").generate(), "");
+        assert_eq!(Document::parse("\n\n#a\n").generate(), "\n\n#a\n");
+    }
+
+    #[test]
+    fn attaches_comments_to_sections() {
+        let document = Document::parse(FILE_STR);
+
+        match &document.nodes[..] {
+            [Node::Trivia(header), Node::Section(s1), Node::Trivia(_), Node::Section(s2)] => {
+                assert_eq!(header.len(), 2);
+                assert_eq!(s1.label(), Some("s1"));
+                assert_eq!(s1.comments.len(), 1);
+                assert_eq!(s1.comments[0].text, "# first section");
+                assert_eq!(s2.label(), Some("s2"));
+                assert!(s2.comments.is_empty());
+                assert_eq!(s1.comments(), vec!["first section", "inner"]);
+            }
+            nodes => panic!("unexpected nodes: {:?}", nodes),
+        }
+    }
+
+    #[test]
+    fn applies_only_changed_lines() {
+        let mut sections = parse_data(FILE_STR).unwrap();
+        sections[1].translations.insert(Language::EN, "en3");
+
+        let mut document = Document::parse(FILE_STR);
+        document.apply(&sections);
+
+        assert_eq!(
+            document.generate(),
+            FILE_STR.replace("\t\"en2\"", "\t\"en3\"")
+        );
+    }
+
+    #[test]
+    fn inserts_missing_language_and_appends_section() {
+        let mut sections = parse_data(FILE_STR).unwrap();
+        sections[0].translations.insert(Language::EN, "en1");
+        let mut s3 = Section::new();
+        s3.label = "s3";
+        s3.translations.insert(Language::PL, "pl3");
+        sections.push(s3);
+
+        let mut document = Document::parse(FILE_STR);
+        document.apply(&sections);
+
+        let expected = FILE_STR
+            .replace(
+                "    PL\t\"pl1\"\r\n",
+                "    PL\t\"pl1\"\r\n    EN\t\"en1\"\r\n",
+            )
+            .to_string()
+            + "\r\n\r\nsection\r\n\tTXT\t\"s3\"\r\n\tPL\t\"pl3\"\r\nend\r\n";
+        assert_eq!(document.generate(), expected);
+        assert_eq!(parse_data(&expected).unwrap(), sections);
+    }
+
+    #[test]
+    fn replaces_comments_with_prefix() {
+        let mut document = Document::parse(FILE_STR);
+        let comments = HashMap::from([("s1", "# sources: PL=a.txt".to_string())]);
+        document.set_comments("# sources:", &comments);
+        let comments = HashMap::from([("s2", "# sources: PL=b.txt".to_string())]);
+        document.set_comments("# sources:", &comments);
+
+        assert_eq!(
+            document.generate(),
+            FILE_STR.replace(
+                "section\r\n\tTXT\t\"s2\"",
+                "# sources: PL=b.txt\r\nsection\r\n\tTXT\t\"s2\""
+            )
+        );
+    }
+
+    #[test]
+    fn removes_dropped_sections_and_translations() {
+        let mut sections = parse_data(FILE_STR).unwrap();
+        sections.remove(0);
+        sections[0].translations.remove(&Language::EN);
+
+        let mut document = Document::parse(FILE_STR);
+        document.apply(&sections);
+
+        assert_eq!(
+            document.generate(),
+            "# header\r\n\r\n\r\nsection\r\n\tTXT\t\"s2\"\r\n\tPL\t\"pl2\"\r\nend"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the tests of several modules.
+
+use crate::section::{Language, Section};
+
+/// Section with the given label and translations.
+pub fn section<'a>(label: &'a str, translations: &[(Language, &'a str)]) -> Section<'a> {
+    let mut s = Section::new();
+    s.label = label;
+    s.translations = translations.iter().copied().collect();
+    s
+}
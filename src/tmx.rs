@@ -0,0 +1,118 @@
+use crate::section::{Language, Section};
+use crate::xliff::{escape_xml, protected_tokens};
+
+/// Escapes the text, wrapping printf placeholders and color codes in `<ph>` elements.
+fn segment(text: &str) -> String {
+    let mut content = String::new();
+    let mut offset = 0;
+
+    for (start, token) in protected_tokens(text) {
+        content.push_str(&escape_xml(&text[offset..start]));
+        content.push_str(&format!("<ph>{}</ph>", escape_xml(token)));
+        offset = start + token.len();
+    }
+    content.push_str(&escape_xml(&text[offset..]));
+
+    content
+}
+
+/// Generates a TMX 1.4 translation memory with a translation unit for every label translated
+/// to at least two of the languages. The first language is the source, units without it take
+/// their first translated language as the source.
+pub fn generate_tmx(sections: &[Section], languages: &[Language]) -> String {
+    let source = languages.first().copied().unwrap_or(Language::PL);
+    let mut tmx = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <tmx version=\"1.4\">\n\
+        \x20 <header creationtool=\"{}\" creationtoolversion=\"{}\" segtype=\"sentence\" \
+        o-tmf=\"locale_string\" adminlang=\"en\" srclang=\"{}\" datatype=\"plaintext\"/>\n\
+        \x20 <body>\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        source.code().to_ascii_lowercase()
+    );
+
+    for section in sections {
+        let translations: Vec<(Language, &str)> = languages
+            .iter()
+            .filter_map(|l| Some((*l, *section.translations.get(l)?)))
+            .collect();
+        if translations.len() < 2 {
+            continue;
+        }
+
+        let srclang = match translations[0].0 {
+            lang if lang == source => String::new(),
+            lang => format!(" srclang=\"{}\"", lang.code().to_ascii_lowercase()),
+        };
+        tmx.push_str(&format!(
+            "    <tu tuid=\"{}\"{}>\n",
+            escape_xml(section.label),
+            srclang
+        ));
+        for (lang, text) in translations {
+            tmx.push_str(&format!(
+                "      <tuv xml:lang=\"{}\"><seg>{}</seg></tuv>\n",
+                lang.code().to_ascii_lowercase(),
+                segment(text)
+            ));
+        }
+        tmx.push_str("    </tu>\n");
+    }
+
+    tmx.push_str("  </body>\n</tmx>\n");
+    tmx
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse_data;
+    use crate::section::Language;
+    use crate::tmx::generate_tmx;
+
+    static FILE_STR: &str = "section\n\
+        \tTXT\t\"gold\"\n\
+        \tPL\t\"Masz |cffffd700%d|r złota\"\n\
+        \tEN\t\"You have |cffffd700%d|r gold\"\n\
+        \tDE\t\"Du & ich\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"sword\"\n\
+        \tPL\t\"Miecz\"\n\
+        \tDE\t\"Schwert\"\n\
+        end\n";
+
+    #[test]
+    fn exports_labels_with_two_languages() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let tmx = generate_tmx(&sections, &[Language::PL, Language::EN]);
+
+        assert!(tmx.contains("srclang=\"pl\""));
+        assert!(tmx.ends_with(
+            "  <body>\n\
+            \x20   <tu tuid=\"gold\">\n\
+            \x20     <tuv xml:lang=\"pl\"><seg>Masz <ph>|cffffd700</ph><ph>%d</ph><ph>|r</ph> złota</seg></tuv>\n\
+            \x20     <tuv xml:lang=\"en\"><seg>You have <ph>|cffffd700</ph><ph>%d</ph><ph>|r</ph> gold</seg></tuv>\n\
+            \x20   </tu>\n\
+            \x20 </body>\n\
+            </tmx>\n"
+        ));
+
+        let tmx = generate_tmx(&sections, &[Language::PL, Language::EN, Language::DE]);
+        assert!(tmx.contains("<tuv xml:lang=\"de\"><seg>Du &amp; ich</seg></tuv>"));
+        assert!(tmx.contains("<tu tuid=\"sword\">"));
+        assert!(roxmltree::Document::parse(&tmx).is_ok());
+    }
+
+    #[test]
+    fn sets_source_of_units_without_source_language() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let tmx = generate_tmx(&sections, &[Language::EN, Language::PL, Language::DE]);
+
+        assert!(tmx.contains("srclang=\"en\""));
+        assert!(tmx.contains("<tu tuid=\"gold\">"));
+        assert!(tmx.contains("<tu tuid=\"sword\" srclang=\"pl\">"));
+        assert!(roxmltree::Document::parse(&tmx).is_ok());
+    }
+}
@@ -0,0 +1,481 @@
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Located, Location};
+use crate::parse::ParseError;
+use crate::placeholder::placeholders;
+use crate::section::{borrowed_section, Language, Section};
+use crate::syntax::Document;
+use roxmltree::Node;
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum XliffError {
+    #[error("invalid XML: {0}")]
+    Xml(String, Location),
+    #[error("XLIFF file does not name a valid target language, found {0:?}")]
+    Language(String, Location),
+    #[error("inline element {0} in unit {1} has no original data")]
+    MissingData(String, String, Location),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl XliffError {
+    /// Sets the name of the file the error comes from.
+    pub fn with_file<T: AsRef<Path>>(mut self, file: T) -> Self {
+        match &mut self {
+            XliffError::Xml(_, l)
+            | XliffError::Language(_, l)
+            | XliffError::MissingData(_, _, l) => l.set_file(file),
+            XliffError::Parse(_) | XliffError::Io(_) => {}
+        }
+
+        self
+    }
+}
+
+impl Located for XliffError {
+    fn location(&self) -> Option<&Location> {
+        match self {
+            XliffError::Xml(_, l)
+            | XliffError::Language(_, l)
+            | XliffError::MissingData(_, _, l) => Some(l),
+            XliffError::Parse(e) => e.location(),
+            XliffError::Io(_) => None,
+        }
+    }
+}
+
+impl From<XliffError> for Diagnostic {
+    fn from(error: XliffError) -> Self {
+        let location = error.location().cloned();
+        let code = match error {
+            XliffError::Xml(..) => "xliff-syntax",
+            XliffError::Language(..) => "xliff-language",
+            XliffError::MissingData(..) => "xliff-missing-data",
+            XliffError::Parse(e) => return e.into(),
+            XliffError::Io(e) => return ParseError::Io(e).into(),
+        };
+
+        Diagnostic::error(code, error.to_string())
+            .with_location(location)
+            .with_kind(DiagnosticKind::Parse)
+    }
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Printf placeholders and `|c`/`|r` color codes of a text with their byte offsets, in order.
+pub fn protected_tokens(text: &str) -> Vec<(usize, &str)> {
+    let mut tokens: Vec<(usize, &str)> = placeholders(text)
+        .iter()
+        .map(|p| (p.offset, p.spec))
+        .collect();
+    let mut offset = 0;
+
+    while let Some(position) = text[offset..].find('|') {
+        let start = offset + position;
+        let rest = &text[start + 1..];
+        let length = match rest.chars().next() {
+            Some('|') => {
+                offset = start + 2;
+                continue;
+            }
+            Some('c')
+                if rest
+                    .get(1..9)
+                    .is_some_and(|color| color.chars().all(|c| c.is_ascii_hexdigit())) =>
+            {
+                10
+            }
+            Some('r') => 2,
+            _ => 1,
+        };
+        if length > 1 {
+            tokens.push((start, &text[start..start + length]));
+        }
+        offset = start + length;
+    }
+
+    tokens.sort();
+    tokens
+}
+
+/// Escapes the text, replacing protected tokens with `<ph>` elements referencing `data`. Each
+/// token takes the id of the first unused equal token in `ids`, other tokens get new ids.
+fn inline<'a>(text: &'a str, data: &mut Vec<&'a str>, ids: &mut Vec<(&'a str, bool)>) -> String {
+    let mut content = String::new();
+    let mut offset = 0;
+
+    for (start, token) in protected_tokens(text) {
+        let id = match ids.iter().position(|(t, used)| *t == token && !used) {
+            Some(i) => i,
+            None => {
+                ids.push((token, false));
+                ids.len() - 1
+            }
+        };
+        ids[id].1 = true;
+        let data_id = match data.iter().position(|d| *d == token) {
+            Some(i) => i,
+            None => {
+                data.push(token);
+                data.len() - 1
+            }
+        };
+
+        content.push_str(&escape_xml(&text[offset..start]));
+        content.push_str(&format!(
+            "<ph id=\"{}\" dataRef=\"d{}\"/>",
+            id + 1,
+            data_id + 1
+        ));
+        offset = start + token.len();
+    }
+    content.push_str(&escape_xml(&text[offset..]));
+
+    content
+}
+
+/// Generates an XLIFF 2.0 file translating `source` into `lang`. Every label becomes a unit
+/// with a single segment, printf placeholders and color codes become `<ph>` elements so they
+/// cannot be edited, and comments of the section become notes.
+pub fn generate_xliff(
+    sections: &[Section],
+    document: &Document,
+    source: Language,
+    lang: Language,
+    original: &str,
+) -> String {
+    let comments: BTreeMap<&str, Vec<&str>> = document
+        .sections()
+        .filter_map(|s| Some((s.label()?, s.comments())))
+        .collect();
+    let mut xliff = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <xliff xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" version=\"2.0\" srcLang=\"{}\" trgLang=\"{}\">\n\
+        \x20 <file id=\"f1\" original=\"{}\">\n",
+        source.code().to_ascii_lowercase(),
+        lang.code().to_ascii_lowercase(),
+        escape_xml(original)
+    );
+
+    for (i, section) in sections.iter().enumerate() {
+        let source_text = section
+            .translations
+            .get(&source)
+            .copied()
+            .unwrap_or(section.label);
+        let mut data = vec![];
+        let mut ids = vec![];
+        let source_content = inline(source_text, &mut data, &mut ids);
+        let target_content = section.translations.get(&lang).map(|text| {
+            let mut target_ids = ids.iter().map(|(t, _)| (*t, false)).collect();
+            inline(text, &mut data, &mut target_ids)
+        });
+
+        xliff.push_str(&format!(
+            "    <unit id=\"u{}\" name=\"{}\">\n",
+            i + 1,
+            escape_xml(section.label)
+        ));
+        if let Some(comments) = comments.get(section.label).filter(|c| !c.is_empty()) {
+            xliff.push_str("      <notes>\n");
+            for comment in comments {
+                xliff.push_str(&format!("        <note>{}</note>\n", escape_xml(comment)));
+            }
+            xliff.push_str("      </notes>\n");
+        }
+        if !data.is_empty() {
+            xliff.push_str("      <originalData>\n");
+            for (id, token) in data.iter().enumerate() {
+                xliff.push_str(&format!(
+                    "        <data id=\"d{}\">{}</data>\n",
+                    id + 1,
+                    escape_xml(token)
+                ));
+            }
+            xliff.push_str("      </originalData>\n");
+        }
+        match &target_content {
+            Some(target) => xliff.push_str(&format!(
+                "      <segment state=\"translated\">\n\
+                \x20       <source>{}</source>\n\
+                \x20       <target>{}</target>\n\
+                \x20     </segment>\n",
+                source_content, target
+            )),
+            None => xliff.push_str(&format!(
+                "      <segment state=\"initial\">\n\
+                \x20       <source>{}</source>\n\
+                \x20     </segment>\n",
+                source_content
+            )),
+        }
+        xliff.push_str("    </unit>\n");
+    }
+
+    xliff.push_str("  </file>\n</xliff>\n");
+    xliff
+}
+
+/// Target texts of an XLIFF file by label.
+#[derive(Debug, PartialEq)]
+pub struct XliffFile {
+    /// Language from the `trgLang` attribute.
+    pub language: Language,
+    pub targets: Vec<(String, String)>,
+}
+
+impl XliffFile {
+    pub fn sections(&self) -> Vec<Section<'_>> {
+        self.targets
+            .iter()
+            .map(|(label, text)| borrowed_section(label, [(self.language, text.as_str())]))
+            .collect()
+    }
+}
+
+fn node_location(node: Node) -> Location {
+    let position = node.document().text_pos_at(node.range().start);
+
+    Location {
+        line: position.row as usize,
+        column: position.col as usize,
+        length: 1,
+        ..Location::default()
+    }
+}
+
+/// Text of an element with inline elements replaced by their original data.
+fn inline_text(
+    node: Node,
+    data: &BTreeMap<&str, String>,
+    label: &str,
+) -> Result<String, XliffError> {
+    let mut text = String::new();
+
+    for child in node.children() {
+        if let Some(t) = child.text().filter(|_| child.is_text()) {
+            text.push_str(t);
+            continue;
+        }
+
+        match child.tag_name().name() {
+            "ph" | "sc" | "ec" => {
+                let original = child
+                    .attribute("dataRef")
+                    .and_then(|id| data.get(id).map(String::as_str))
+                    .or_else(|| child.attribute("equiv"))
+                    .ok_or_else(|| {
+                        XliffError::MissingData(
+                            child.attribute("id").unwrap_or("").to_string(),
+                            label.to_string(),
+                            node_location(child),
+                        )
+                    })?;
+                text.push_str(original);
+            }
+            _ if child.is_element() => text.push_str(&inline_text(child, data, label)?),
+            _ => {}
+        }
+    }
+
+    Ok(text)
+}
+
+/// Parses the targets of an XLIFF 2.0 file. Units are labelled by their `name`, or by their
+/// `id` when they have no name. Units with an untranslated segment or an empty target are
+/// skipped.
+pub fn parse_xliff(data: &str) -> Result<XliffFile, XliffError> {
+    let document = roxmltree::Document::parse(data).map_err(|e| {
+        let position = e.pos();
+        let location = Location {
+            line: position.row as usize,
+            column: position.col as usize,
+            length: 1,
+            ..Location::default()
+        };
+        XliffError::Xml(e.to_string(), location)
+    })?;
+    let root = document.root_element();
+    let tag = root.attribute("trgLang").unwrap_or("");
+    let language = Language::from_tag(tag)
+        .ok_or_else(|| XliffError::Language(tag.to_string(), node_location(root)))?;
+    let mut targets = vec![];
+
+    for unit in root.descendants().filter(|n| n.has_tag_name("unit")) {
+        let label = match unit.attribute("name").or_else(|| unit.attribute("id")) {
+            Some(label) => label,
+            None => continue,
+        };
+        let data: BTreeMap<&str, String> = unit
+            .descendants()
+            .filter(|n| n.has_tag_name("data"))
+            .filter_map(|n| {
+                let text: String = n
+                    .descendants()
+                    .filter(|t| t.is_text())
+                    .filter_map(|t| t.text())
+                    .collect();
+                Some((n.attribute("id")?, text))
+            })
+            .collect();
+
+        let mut text = String::new();
+        let mut translated = true;
+        for segment in unit
+            .children()
+            .filter(|n| n.has_tag_name("segment") || n.has_tag_name("ignorable"))
+        {
+            let child = |name| segment.children().find(|n| n.has_tag_name(name));
+            let target = match (child("target"), segment.has_tag_name("ignorable")) {
+                (Some(target), _) => target,
+                (None, true) => match child("source") {
+                    Some(source) => source,
+                    None => continue,
+                },
+                (None, false) => {
+                    translated = false;
+                    break;
+                }
+            };
+            if segment.attribute("state") == Some("initial") {
+                translated = false;
+                break;
+            }
+            text.push_str(&inline_text(target, &data, label)?);
+        }
+
+        if translated && !text.is_empty() {
+            targets.push((label.to_string(), text));
+        }
+    }
+
+    Ok(XliffFile { language, targets })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse_data;
+    use crate::section::Language;
+    use crate::syntax::Document;
+    use crate::xliff::{generate_xliff, parse_xliff, XliffError};
+
+    static FILE_STR: &str = "# shown in the shop\n\
+        section\n\
+        \tTXT\t\"gold\"\n\
+        \tPL\t\"Masz |cffffd700%d|r złota & %s\"\n\
+        \tEN\t\"%s & |cffffd700%d|r gold\"\n\
+        end\n\
+        \n\
+        section\n\
+        \tTXT\t\"sword\"\n\
+        \tPL\t\"Miecz <100%%>\"\n\
+        end\n";
+
+    #[test]
+    fn protects_placeholders_and_colors() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let xliff = generate_xliff(
+            &sections,
+            &Document::parse(FILE_STR),
+            Language::PL,
+            Language::EN,
+            "locale_string.txt",
+        );
+
+        assert!(xliff.contains("srcLang=\"pl\" trgLang=\"en\""));
+        assert!(xliff.contains(
+            "    <unit id=\"u1\" name=\"gold\">\n\
+            \x20     <notes>\n\
+            \x20       <note>shown in the shop</note>\n\
+            \x20     </notes>\n\
+            \x20     <originalData>\n\
+            \x20       <data id=\"d1\">|cffffd700</data>\n\
+            \x20       <data id=\"d2\">%d</data>\n\
+            \x20       <data id=\"d3\">|r</data>\n\
+            \x20       <data id=\"d4\">%s</data>\n\
+            \x20     </originalData>\n\
+            \x20     <segment state=\"translated\">\n\
+            \x20       <source>Masz <ph id=\"1\" dataRef=\"d1\"/><ph id=\"2\" dataRef=\"d2\"/><ph id=\"3\" dataRef=\"d3\"/> złota &amp; <ph id=\"4\" dataRef=\"d4\"/></source>\n\
+            \x20       <target><ph id=\"4\" dataRef=\"d4\"/> &amp; <ph id=\"1\" dataRef=\"d1\"/><ph id=\"2\" dataRef=\"d2\"/><ph id=\"3\" dataRef=\"d3\"/> gold</target>\n\
+            \x20     </segment>\n\
+            \x20   </unit>\n"
+        ));
+        assert!(xliff.contains(
+            "      <segment state=\"initial\">\n\
+            \x20       <source>Miecz &lt;100%%&gt;</source>\n\
+            \x20     </segment>\n"
+        ));
+    }
+
+    #[test]
+    fn round_trips_targets() {
+        let sections = parse_data(FILE_STR).unwrap();
+        let xliff = generate_xliff(
+            &sections,
+            &Document::parse(FILE_STR),
+            Language::PL,
+            Language::EN,
+            "locale_string.txt",
+        );
+        let imported = parse_xliff(&xliff).unwrap();
+
+        assert_eq!(imported.language, Language::EN);
+        assert_eq!(
+            imported.targets,
+            vec![("gold".to_string(), "%s & |cffffd700%d|r gold".to_string())]
+        );
+        assert_eq!(
+            imported.sections()[0].translations[&Language::EN],
+            sections[0].translations[&Language::EN]
+        );
+    }
+
+    #[test]
+    fn reads_vendor_markup() {
+        let xliff = parse_xliff(
+            "<xliff xmlns=\"urn:oasis:names:tc:xliff:document:2.0\" version=\"2.0\" srcLang=\"pl\" trgLang=\"de-DE\">\n\
+            <file id=\"f\"><unit id=\"greeting\">\n\
+            <segment><source>Cześć</source><target><mrk id=\"m1\">Hallo</mrk></target></segment>\n\
+            <ignorable><source> </source></ignorable>\n\
+            <segment><source>%s</source><target><ph id=\"1\" equiv=\"%s\"/>!</target></segment>\n\
+            </unit></file></xliff>",
+        )
+        .unwrap();
+
+        assert_eq!(xliff.language, Language::DE);
+        assert_eq!(
+            xliff.targets,
+            vec![("greeting".to_string(), "Hallo %s!".to_string())]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        assert!(matches!(
+            parse_xliff("<xliff trgLang=\"en\">\n<file></unit></xliff>"),
+            Err(XliffError::Xml(_, l)) if l.line == 2
+        ));
+        assert!(matches!(
+            parse_xliff("<xliff srcLang=\"pl\"/>"),
+            Err(XliffError::Language(..))
+        ));
+        assert!(matches!(
+            parse_xliff(
+                "<xliff trgLang=\"en\"><file><unit id=\"a\">\n\
+                <segment><source/><target><ph id=\"1\" dataRef=\"d9\"/></target></segment>\n\
+                </unit></file></xliff>"
+            ),
+            Err(XliffError::MissingData(id, label, l)) if id == "1" && label == "a" && l.line == 2
+        ));
+    }
+}